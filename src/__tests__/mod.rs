@@ -1,14 +1,89 @@
 #[cfg(test)]
 mod tests {
+    use std::{future::Future, io::Write, sync::{atomic::{AtomicBool, Ordering}, Arc, Condvar, Mutex}, time::Duration};
+
+    use prost::Message;
     use rust_decimal::Decimal;
 
-    use crate::engine::models::{order::{Order, OrderId, OrderPrice, OrderQuantity, OrderSide}, orderbook::Orderbook};
+    use crate::{candle::{service::CandleService, CandleInterval}, balance::{repositories::memory::MemoryBalanceManager, service::{BalanceService, BusinessType}, AssetId, BalanceDelta, BalanceEntry, BalanceSourceExector, BalanceStatus, BalanceType, UserId}, common::{errors::{AppError, AppResult}, sequencer::Sequencer, time::Time}, config::{ApiKeyConfig, Config, FeeTierConfig, JournalConfig, LedgerConfig, MarketConfig, SnapshotConfig}, container::Container, engine::{models::{market::{Market, PairId}, order::{NewOrder, Order, OrderId, OrderPrice, OrderQuantity, OrderSide, OrderStatus, PostOnlyMode, SelfTradePreventionMode, TimeInForce}, orderbook::{DepthLevel, Orderbook}, trade::{Trade, TradeEvent, TradeId}}, service::EngineService}, fee::{service::FeeService, FeeRates}, journal::{repositories::memory::MemoryJournalManager, service::JournalService, Command, CommandContext, JournalEntry, JournalSourceExector}, ledger::{LedgerEntry, LedgerEntryId, LedgerFilter}, notification::{service::NotificationService, OrderEventType, UserEvent}, presentation::grpc::{auth::Authenticator, server::match_engine::ErrorDetail, validation::{parse_fee_rate, parse_positive_decimal, parse_taker_fee_rate}}};
+    use tokio::sync::broadcast;
+    use tonic::Request;
 
+    const BASE_ASSET_ID: AssetId = 1;
+    const QUOTE_ASSET_ID: AssetId = 2;
 
     fn new_empty_orderbook() -> Orderbook {
         Orderbook::new()
     }
 
+    fn new_balance_service() -> Arc<BalanceService> {
+        let balance_source: Arc<Box<dyn BalanceSourceExector>> = Arc::new(Box::new(MemoryBalanceManager::new()));
+
+        Arc::new(BalanceService::new(balance_source))
+    }
+
+    fn new_market_config() -> MarketConfig {
+        MarketConfig { pair_id: 1, base_asset_id: BASE_ASSET_ID, quote_asset_id: QUOTE_ASSET_ID, is_market_trade_enabled: true, ..Default::default() }
+    }
+
+    fn new_market(balance_service: Arc<BalanceService>) -> Market {
+        Market::new(&new_market_config(), balance_service, Arc::new(FeeService::new(vec![])), Arc::new(Sequencer::new()), Arc::new(Sequencer::new()))
+    }
+
+    fn deposit(balance_service: &BalanceService, user_id: UserId, asset_id: AssetId, amount: Decimal) {
+        balance_service.change_balance(new_context(), user_id, asset_id, BusinessType::Deposit, 1, BalanceType::Available, amount).unwrap();
+    }
+
+    fn new_limit_order_input(user_id: UserId, side: OrderSide, limit_price: OrderPrice, quantity: OrderQuantity) -> NewOrder {
+        NewOrder { user_id, side, limit_price: Some(limit_price), quantity, time_in_force: TimeInForce::GoodTillCancel, post_only: None, stop_price: None, quote_amount: None, self_trade_prevention: None }
+    }
+
+    fn drain_user_events(receiver: &mut broadcast::Receiver<UserEvent>) -> (Vec<OrderEventType>, usize) {
+        let mut order_events = vec![];
+        let mut balance_events = 0;
+
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                UserEvent::Order(event) => order_events.push(event.type_),
+                UserEvent::Balance(_) => balance_events += 1,
+            }
+        }
+
+        (order_events, balance_events)
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    // Balance source whose balance moves wait until a second one is in flight, to prove commands overlap
+    struct RendezvousBalanceSource { inner: MemoryBalanceManager, arrivals: Mutex<usize>, arrived: Condvar, is_timed_out: Arc<AtomicBool> }
+
+    impl BalanceSourceExector for RendezvousBalanceSource {
+        fn get(&self, user_id: UserId, type_: BalanceType, asset_id: AssetId) -> Decimal { self.inner.get(user_id, type_, asset_id) }
+        fn increase(&self, user_id: UserId, type_: BalanceType, asset_id: AssetId, amount: Decimal) -> AppResult<BalanceStatus> { self.inner.increase(user_id, type_, asset_id, amount) }
+        fn decrease(&self, user_id: UserId, type_: BalanceType, asset_id: AssetId, amount: Decimal) -> AppResult<BalanceStatus> { self.inner.decrease(user_id, type_, asset_id, amount) }
+        fn get_total(&self, user_id: UserId, asset_id: AssetId) -> Decimal { self.inner.get_total(user_id, asset_id) }
+        fn get_status(&self, user_id: UserId, asset_id: AssetId) -> BalanceStatus { self.inner.get_status(user_id, asset_id) }
+        fn get_all(&self) -> Vec<BalanceEntry> { self.inner.get_all() }
+        fn restore(&self, entries: Vec<BalanceEntry>) { self.inner.restore(entries) }
+
+        fn apply_deltas(&self, deltas: &[BalanceDelta]) -> AppResult<Vec<BalanceStatus>> {
+            let mut arrivals = self.arrivals.lock().unwrap();
+            *arrivals += 1;
+            self.arrived.notify_all();
+
+            let (_arrivals, wait) = self.arrived.wait_timeout_while(arrivals, Duration::from_secs(5), |arrivals| *arrivals < 2).unwrap();
+            if wait.timed_out() { self.is_timed_out.store(true, Ordering::Relaxed); }
+
+            self.inner.apply_deltas(deltas)
+        }
+    }
+
+    fn new_context() -> CommandContext {
+        CommandContext { journal_sequence: 0, timestamp: Time::get_current_timestamp() }
+    }
+
     fn new_market_order(id: OrderId, side: OrderSide, quantity: OrderQuantity) -> Order {
         Order::new_market(id, 0, 0, 0, side, quantity)
     }
@@ -17,6 +92,10 @@ mod tests {
         Order::new_limit(id, 0, 0, 0, side, limit_price, quantity)
     }
 
+    fn to_depth(depth_levels: Vec<DepthLevel>) -> Vec<[Decimal; 2]> {
+        depth_levels.iter().map(|depth_level| [depth_level.price, depth_level.quantity]).collect()
+    }
+
     #[test]
     // Add bid market message to an empty lob. Order expires
     fn order_should_expires_for_add_bid_market_to_empty_orderbook() {
@@ -25,8 +104,8 @@ mod tests {
         let match_result = orderbook.put_order(new_market_order(0, OrderSide::Bid, Decimal::from(1000))).unwrap();
 
         assert!(match_result.trades.is_empty());
-        assert!(orderbook.get_asks_depth().is_empty());
-        assert!(orderbook.get_bids_depth().is_empty());
+        assert!(orderbook.get_asks_levels(None, None).is_empty());
+        assert!(orderbook.get_bids_levels(None, None).is_empty());
     }
 
     #[test]
@@ -37,8 +116,8 @@ mod tests {
         let match_result = orderbook.put_order(new_market_order(0, OrderSide::Ask, Decimal::from(1000))).unwrap();
 
         assert!(match_result.trades.is_empty());
-        assert!(orderbook.get_asks_depth().is_empty());
-        assert!(orderbook.get_bids_depth().is_empty());
+        assert!(orderbook.get_asks_levels(None, None).is_empty());
+        assert!(orderbook.get_bids_levels(None, None).is_empty());
     }
 
     #[test]
@@ -62,8 +141,8 @@ mod tests {
         assert_eq!(match_result.trades[1].get_quantity(), Decimal::from(500));
         assert_eq!(match_result.trades[2].get_quantity(), Decimal::from(300));
 
-        assert_eq!(orderbook.get_asks_depth(), vec![[Decimal::from(100), Decimal::from(700)]]);
-        assert!(orderbook.get_bids_depth().is_empty());
+        assert_eq!(to_depth(orderbook.get_asks_levels(None, None)), vec![[Decimal::from(100), Decimal::from(700)]]);
+        assert!(orderbook.get_bids_levels(None, None).is_empty());
     }
 
     #[test]
@@ -83,8 +162,8 @@ mod tests {
 
         assert_eq!(match_result.trades[0].get_quantity(), Decimal::from(1000));
 
-        assert_eq!(orderbook.get_bids_depth(), vec![[Decimal::from(80), Decimal::from(500)], [Decimal::from(50), Decimal::from(200)]]);
-        assert!(orderbook.get_asks_depth().is_empty());
+        assert_eq!(to_depth(orderbook.get_bids_levels(None, None)), vec![[Decimal::from(80), Decimal::from(500)], [Decimal::from(50), Decimal::from(200)]]);
+        assert!(orderbook.get_asks_levels(None, None).is_empty());
     }
 
     #[test]
@@ -105,8 +184,8 @@ mod tests {
         assert_eq!(match_result.trades[0].get_quantity(), Decimal::from(500));
         assert_eq!(match_result.trades[1].get_quantity(), Decimal::from(500));
 
-        assert!(orderbook.get_bids_depth().is_empty());
-        assert!(orderbook.get_asks_depth().is_empty());
+        assert!(orderbook.get_bids_levels(None, None).is_empty());
+        assert!(orderbook.get_asks_levels(None, None).is_empty());
     }
 
     #[test]
@@ -127,8 +206,8 @@ mod tests {
         assert_eq!(match_result.trades[0].get_quantity(), Decimal::from(500));
         assert_eq!(match_result.trades[1].get_quantity(), Decimal::from(500));
 
-        assert!(orderbook.get_bids_depth().is_empty());
-        assert!(orderbook.get_asks_depth().is_empty());
+        assert!(orderbook.get_bids_levels(None, None).is_empty());
+        assert!(orderbook.get_asks_levels(None, None).is_empty());
     }
 
     #[test]
@@ -140,8 +219,8 @@ mod tests {
 
         assert!(match_result.trades.is_empty());
 
-        assert_eq!(orderbook.get_bids_depth(), vec![[Decimal::from(100), Decimal::from(1000)]]);
-        assert!(orderbook.get_asks_depth().is_empty());
+        assert_eq!(to_depth(orderbook.get_bids_levels(None, None)), vec![[Decimal::from(100), Decimal::from(1000)]]);
+        assert!(orderbook.get_asks_levels(None, None).is_empty());
     }
 
     #[test]
@@ -155,8 +234,8 @@ mod tests {
     
         assert!(match_result.trades.is_empty());
 
-        assert_eq!(orderbook.get_bids_depth(), vec![[Decimal::from(200), Decimal::from(500)], [Decimal::from(100), Decimal::from(1000)]]);
-        assert!(orderbook.get_asks_depth().is_empty());
+        assert_eq!(to_depth(orderbook.get_bids_levels(None, None)), vec![[Decimal::from(200), Decimal::from(500)], [Decimal::from(100), Decimal::from(1000)]]);
+        assert!(orderbook.get_asks_levels(None, None).is_empty());
     }
 
     #[test]
@@ -170,8 +249,8 @@ mod tests {
 
         assert!(match_result.trades.is_empty());
 
-        assert_eq!(orderbook.get_bids_depth(), vec![[Decimal::from(100), Decimal::from(2000)]]);
-        assert!(orderbook.get_asks_depth().is_empty());
+        assert_eq!(to_depth(orderbook.get_bids_levels(None, None)), vec![[Decimal::from(100), Decimal::from(2000)]]);
+        assert!(orderbook.get_asks_levels(None, None).is_empty());
     }
 
     #[test]
@@ -185,8 +264,8 @@ mod tests {
 
         assert!(match_result.trades.is_empty());
 
-        assert_eq!(orderbook.get_bids_depth(), vec![[Decimal::from(50), Decimal::from(1000)]]);
-        assert_eq!(orderbook.get_asks_depth(), vec![[Decimal::from(100), Decimal::from(1000)]]);
+        assert_eq!(to_depth(orderbook.get_bids_levels(None, None)), vec![[Decimal::from(50), Decimal::from(1000)]]);
+        assert_eq!(to_depth(orderbook.get_asks_levels(None, None)), vec![[Decimal::from(100), Decimal::from(1000)]]);
     }
 
     #[test]
@@ -208,8 +287,8 @@ mod tests {
         assert_eq!(match_result.trades[0].get_quantity(), Decimal::from(200));
         assert_eq!(match_result.trades[1].get_quantity(), Decimal::from(500));
 
-        assert_eq!(orderbook.get_bids_depth(), vec![[Decimal::from(80), Decimal::from(300)]]);
-        assert_eq!(orderbook.get_asks_depth(), vec![[Decimal::from(100), Decimal::from(1000)]]);
+        assert_eq!(to_depth(orderbook.get_bids_levels(None, None)), vec![[Decimal::from(80), Decimal::from(300)]]);
+        assert_eq!(to_depth(orderbook.get_asks_levels(None, None)), vec![[Decimal::from(100), Decimal::from(1000)]]);
     }
 
     #[test]
@@ -232,8 +311,8 @@ mod tests {
         assert_eq!(match_result.trades[0].get_quantity(), Decimal::from(200));
         assert_eq!(match_result.trades[1].get_quantity(), Decimal::from(300));
 
-        assert_eq!(orderbook.get_asks_depth(), vec![[Decimal::from(50), Decimal::from(300)], [Decimal::from(100), Decimal::from(1000)]]);
-        assert!(orderbook.get_bids_depth().is_empty());
+        assert_eq!(to_depth(orderbook.get_asks_levels(None, None)), vec![[Decimal::from(50), Decimal::from(300)], [Decimal::from(100), Decimal::from(1000)]]);
+        assert!(orderbook.get_bids_levels(None, None).is_empty());
     }
 
     #[test]
@@ -245,8 +324,8 @@ mod tests {
 
         assert!(match_result.trades.is_empty());
 
-        assert_eq!(orderbook.get_asks_depth(), vec![[Decimal::from(100), Decimal::from(1000)]]);
-        assert!(orderbook.get_bids_depth().is_empty());
+        assert_eq!(to_depth(orderbook.get_asks_levels(None, None)), vec![[Decimal::from(100), Decimal::from(1000)]]);
+        assert!(orderbook.get_bids_levels(None, None).is_empty());
     }
 
     #[test]
@@ -260,8 +339,8 @@ mod tests {
     
         assert!(match_result.trades.is_empty());
 
-        assert_eq!(orderbook.get_asks_depth(), vec![[Decimal::from(100), Decimal::from(1000)], [Decimal::from(200), Decimal::from(500)]]);
-        assert!(orderbook.get_bids_depth().is_empty());
+        assert_eq!(to_depth(orderbook.get_asks_levels(None, None)), vec![[Decimal::from(100), Decimal::from(1000)], [Decimal::from(200), Decimal::from(500)]]);
+        assert!(orderbook.get_bids_levels(None, None).is_empty());
     }
 
     #[test]
@@ -275,8 +354,8 @@ mod tests {
 
         assert!(match_result.trades.is_empty());
 
-        assert_eq!(orderbook.get_asks_depth(), vec![[Decimal::from(100), Decimal::from(2000)]]);
-        assert!(orderbook.get_bids_depth().is_empty());
+        assert_eq!(to_depth(orderbook.get_asks_levels(None, None)), vec![[Decimal::from(100), Decimal::from(2000)]]);
+        assert!(orderbook.get_bids_levels(None, None).is_empty());
     }
 
     #[test]
@@ -290,8 +369,8 @@ mod tests {
 
         assert!(match_result.trades.is_empty());
 
-        assert_eq!(orderbook.get_bids_depth(), vec![[Decimal::from(50), Decimal::from(1000)]]);
-        assert_eq!(orderbook.get_asks_depth(), vec![[Decimal::from(100), Decimal::from(1000)]]);
+        assert_eq!(to_depth(orderbook.get_bids_levels(None, None)), vec![[Decimal::from(50), Decimal::from(1000)]]);
+        assert_eq!(to_depth(orderbook.get_asks_levels(None, None)), vec![[Decimal::from(100), Decimal::from(1000)]]);
     }
 
     #[test]
@@ -313,8 +392,8 @@ mod tests {
         assert_eq!(match_result.trades[0].get_quantity(), Decimal::from(500));
         assert_eq!(match_result.trades[1].get_quantity(), Decimal::from(200));
 
-        assert_eq!(orderbook.get_bids_depth(), vec![[Decimal::from(50), Decimal::from(200)]]);
-        assert_eq!(orderbook.get_asks_depth(), vec![[Decimal::from(80), Decimal::from(300)]]);
+        assert_eq!(to_depth(orderbook.get_bids_levels(None, None)), vec![[Decimal::from(50), Decimal::from(200)]]);
+        assert_eq!(to_depth(orderbook.get_asks_levels(None, None)), vec![[Decimal::from(80), Decimal::from(300)]]);
     }
 
     #[test]
@@ -337,8 +416,8 @@ mod tests {
         assert_eq!(match_result.trades[0].get_quantity(), Decimal::from(700));
         assert_eq!(match_result.trades[1].get_quantity(), Decimal::from(300));
 
-        assert_eq!(orderbook.get_bids_depth(), vec![[Decimal::from(50), Decimal::from(300)], [Decimal::from(20), Decimal::from(200)]]);
-        assert!(orderbook.get_asks_depth().is_empty());
+        assert_eq!(to_depth(orderbook.get_bids_levels(None, None)), vec![[Decimal::from(50), Decimal::from(300)], [Decimal::from(20), Decimal::from(200)]]);
+        assert!(orderbook.get_asks_levels(None, None).is_empty());
     }
 
     #[test]
@@ -347,7 +426,7 @@ mod tests {
     fn cancel_order_should_panic_for_empty_orderbook() {
         let mut orderbook = new_empty_orderbook();
 
-        orderbook.cancel_order(0).unwrap()
+        orderbook.cancel_order(0).unwrap();
     }
 
     #[test]
@@ -361,7 +440,1288 @@ mod tests {
 
         orderbook.cancel_order(1).unwrap();
 
-        assert_eq!(orderbook.get_bids_depth(), vec![[Decimal::from(107), Decimal::from(3000)], [Decimal::from(100), Decimal::from(1000)]]);
-        assert!(orderbook.get_asks_depth().is_empty());
+        assert_eq!(to_depth(orderbook.get_bids_levels(None, None)), vec![[Decimal::from(107), Decimal::from(3000)], [Decimal::from(100), Decimal::from(1000)]]);
+        assert!(orderbook.get_asks_levels(None, None).is_empty());
+    }
+
+    #[test]
+    // Cancel booked limit order through market. Frozen balance released
+    fn cancel_order_for_market_should_release_frozen_balance() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 1, QUOTE_ASSET_ID, Decimal::from(1000));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Bid, Decimal::from(10), Decimal::from(40))).unwrap();
+
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).frozen, Decimal::from(400));
+
+        let cancelled_order = market.cancel_order(new_context(), 1, 1).unwrap();
+
+        assert_eq!(cancelled_order.get_status(), OrderStatus::Cancelled);
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(1000));
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).frozen, Decimal::from(0));
+        assert!(market.get_orderbook_state(None, None).bids.is_empty());
+    }
+
+    #[test]
+    // Add immediate or cancel bid limit through market. Execution report returned with trades and average price
+    fn process_new_order_should_return_execution_report() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(10));
+        deposit(&balance_service, 2, QUOTE_ASSET_ID, Decimal::from(2000));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(5))).unwrap();
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(102), Decimal::from(5))).unwrap();
+
+        let match_result = market.process_new_order(new_context(), NewOrder { time_in_force: TimeInForce::ImmediateOrCancel, ..new_limit_order_input(2, OrderSide::Bid, Decimal::from(102), Decimal::from(12)) }).unwrap();
+
+        assert_eq!(match_result.taker_order.get_id(), 3);
+        assert_eq!(match_result.taker_order.get_status(), OrderStatus::Cancelled);
+        assert_eq!(match_result.taker_order.get_filled_quantity(), Decimal::from(10));
+        assert_eq!(match_result.taker_order.get_remaining_quantity(), Decimal::from(2));
+        assert_eq!(match_result.get_average_price(), Some(Decimal::from(101)));
+        assert_eq!(match_result.trades.iter().map(|trade| trade.get_maker_order().get_id()).collect::<Vec<OrderId>>(), vec![1, 2]);
+    }
+
+    #[test]
+    // Match bid limit with two offer limits. Trades get increasing ids carried into balance changes
+    fn trades_should_have_sequential_ids_per_market() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(20));
+        deposit(&balance_service, 2, QUOTE_ASSET_ID, Decimal::from(4000));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(5))).unwrap();
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(5))).unwrap();
+
+        let first_result = market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Bid, Decimal::from(100), Decimal::from(8))).unwrap();
+        let second_result = market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Bid, Decimal::from(100), Decimal::from(1))).unwrap();
+
+        assert_eq!(first_result.trades.iter().map(|trade| trade.get_id()).collect::<Vec<TradeId>>(), vec![1, 2]);
+        assert_eq!(second_result.trades.iter().map(|trade| trade.get_id()).collect::<Vec<TradeId>>(), vec![3]);
+        assert!(second_result.trades[0].get_created_at() > 0);
+
+        let outputs = market.transfer_trade_balance(&second_result.trades[0]).unwrap();
+
+        assert!(outputs.iter().all(|output| output.business_type == BusinessType::Trade && output.business_id == 3));
+    }
+
+    #[test]
+    // Match bid limit with offer limit. Trade published to market subscribers
+    fn trades_should_be_published_to_subscribers() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+        let mut receiver = market.subscribe_trades();
+
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(10));
+        deposit(&balance_service, 2, QUOTE_ASSET_ID, Decimal::from(1000));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(4))).unwrap();
+
+        assert!(receiver.try_recv().is_err());
+
+        market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Bid, Decimal::from(100), Decimal::from(3))).unwrap();
+
+        let event = receiver.try_recv().unwrap();
+
+        assert_eq!(event.pair_id, 1);
+        assert_eq!(event.trade_id, 1);
+        assert_eq!(event.price, Decimal::from(100));
+        assert_eq!(event.quantity, Decimal::from(3));
+        assert!(matches!(event.taker_side, OrderSide::Bid));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    // Book, match and cancel offer limits. Price level deltas published with increasing sequence
+    fn orderbook_deltas_should_be_published_with_sequence() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(10));
+        deposit(&balance_service, 2, QUOTE_ASSET_ID, Decimal::from(1000));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(4))).unwrap();
+
+        let (state, mut receiver) = market.subscribe_orderbook();
+
+        assert_eq!(state.sequence, 1);
+        assert_eq!(state.asks, vec![DepthLevel { price: Decimal::from(100), quantity: Decimal::from(4), order_count: 1 }]);
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(101), Decimal::from(2))).unwrap();
+        market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Bid, Decimal::from(101), Decimal::from(5))).unwrap();
+        market.cancel_order(new_context(), 1, 2).unwrap();
+
+        let mut deltas = vec![];
+
+        while let Ok(delta) = receiver.try_recv() {
+            deltas.push((delta.sequence, delta.side, delta.price, delta.quantity));
+        }
+
+        assert_eq!(deltas, vec![
+            (2, OrderSide::Ask, Decimal::from(101), Decimal::from(2)),
+            (3, OrderSide::Ask, Decimal::from(100), Decimal::from(0)),
+            (4, OrderSide::Ask, Decimal::from(101), Decimal::from(1)),
+            (5, OrderSide::Ask, Decimal::from(101), Decimal::from(0)),
+        ]);
+    }
+
+    #[test]
+    // Book limits on both sides. Depth limited to top levels and grouped outward per side
+    fn orderbook_levels_should_honor_depth_and_price_group() {
+        let mut orderbook = new_empty_orderbook();
+
+        orderbook.put_order(new_limit_order(1, OrderSide::Ask, Decimal::new(1001, 1), Decimal::from(1))).unwrap();
+        orderbook.put_order(new_limit_order(2, OrderSide::Ask, Decimal::new(1004, 1), Decimal::from(2))).unwrap();
+        orderbook.put_order(new_limit_order(3, OrderSide::Ask, Decimal::new(1012, 1), Decimal::from(3))).unwrap();
+        orderbook.put_order(new_limit_order(4, OrderSide::Bid, Decimal::new(999, 1), Decimal::from(4))).unwrap();
+        orderbook.put_order(new_limit_order(5, OrderSide::Bid, Decimal::new(991, 1), Decimal::from(5))).unwrap();
+        orderbook.put_order(new_limit_order(6, OrderSide::Bid, Decimal::new(989, 1), Decimal::from(6))).unwrap();
+
+        assert_eq!(orderbook.get_asks_levels(Some(2), None), vec![
+            DepthLevel { price: Decimal::new(1001, 1), quantity: Decimal::from(1), order_count: 1 },
+            DepthLevel { price: Decimal::new(1004, 1), quantity: Decimal::from(2), order_count: 1 },
+        ]);
+        assert_eq!(orderbook.get_asks_levels(Some(1), Some(Decimal::from(1))), vec![DepthLevel { price: Decimal::from(101), quantity: Decimal::from(3), order_count: 2 }]);
+        assert_eq!(orderbook.get_bids_levels(None, Some(Decimal::from(1))), vec![
+            DepthLevel { price: Decimal::from(99), quantity: Decimal::from(9), order_count: 2 },
+            DepthLevel { price: Decimal::from(98), quantity: Decimal::from(6), order_count: 1 },
+        ]);
+    }
+
+    #[test]
+    // Cancel order of another user. Cancel request rejected
+    fn cancel_order_for_market_should_reject_other_user() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(100));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(10), Decimal::from(40))).unwrap();
+
+        assert!(matches!(market.cancel_order(new_context(), 2, 1), Err(AppError::OrderUserMismatch)));
+        assert_eq!(to_depth(market.get_orderbook_state(None, None).asks), vec![[Decimal::from(10), Decimal::from(40)]]);
+    }
+
+    #[test]
+    // Add immediate or cancel bid limit message. Remaining quantity is dropped
+    fn order_should_not_booked_for_immediate_or_cancel_bid_limit() {
+        let mut orderbook = new_empty_orderbook();
+
+        orderbook.put_order(new_limit_order(0, OrderSide::Ask, Decimal::from(100), Decimal::from(500))).unwrap();
+
+        let match_result = orderbook.put_order(new_limit_order(1, OrderSide::Bid, Decimal::from(100), Decimal::from(1000)).with_time_in_force(TimeInForce::ImmediateOrCancel)).unwrap();
+
+        assert_eq!(match_result.trades.len(), 1);
+        assert_eq!(match_result.trades[0].get_quantity(), Decimal::from(500));
+
+        assert!(orderbook.get_asks_levels(None, None).is_empty());
+        assert!(orderbook.get_bids_levels(None, None).is_empty());
+    }
+
+    #[test]
+    // Add fill or kill bid limit message without enough liquidity. Order rejected
+    fn order_should_rejected_for_fill_or_kill_bid_limit_without_liquidity() {
+        let mut orderbook = new_empty_orderbook();
+
+        orderbook.put_order(new_limit_order(0, OrderSide::Ask, Decimal::from(100), Decimal::from(500))).unwrap();
+        orderbook.put_order(new_limit_order(1, OrderSide::Ask, Decimal::from(120), Decimal::from(500))).unwrap();
+
+        let match_result = orderbook.put_order(new_limit_order(2, OrderSide::Bid, Decimal::from(110), Decimal::from(1000)).with_time_in_force(TimeInForce::FillOrKill));
+
+        assert!(matches!(match_result, Err(AppError::FillOrKillOrderNotFilled)));
+
+        assert_eq!(to_depth(orderbook.get_asks_levels(None, None)), vec![[Decimal::from(100), Decimal::from(500)], [Decimal::from(120), Decimal::from(500)]]);
+        assert!(orderbook.get_bids_levels(None, None).is_empty());
+    }
+
+    #[test]
+    // Add fill or kill offer limit message with enough liquidity. Order is filled
+    fn order_should_filled_for_fill_or_kill_ask_limit() {
+        let mut orderbook = new_empty_orderbook();
+
+        orderbook.put_order(new_limit_order(0, OrderSide::Bid, Decimal::from(100), Decimal::from(500))).unwrap();
+        orderbook.put_order(new_limit_order(1, OrderSide::Bid, Decimal::from(90), Decimal::from(700))).unwrap();
+
+        let match_result = orderbook.put_order(new_limit_order(2, OrderSide::Ask, Decimal::from(90), Decimal::from(1000)).with_time_in_force(TimeInForce::FillOrKill)).unwrap();
+
+        assert_eq!(match_result.trades.len(), 2);
+        assert_eq!(match_result.trades[1].get_quantity(), Decimal::from(500));
+
+        assert_eq!(to_depth(orderbook.get_bids_levels(None, None)), vec![[Decimal::from(90), Decimal::from(200)]]);
+        assert!(orderbook.get_asks_levels(None, None).is_empty());
+    }
+
+    #[test]
+    // Add post only bid limit message crossing the offer side. Order rejected
+    fn order_should_rejected_for_crossing_post_only_bid_limit() {
+        let mut orderbook = new_empty_orderbook();
+
+        orderbook.put_order(new_limit_order(0, OrderSide::Ask, Decimal::from(100), Decimal::from(500))).unwrap();
+
+        let match_result = orderbook.put_order(new_limit_order(1, OrderSide::Bid, Decimal::from(100), Decimal::from(500)).with_post_only(Some(PostOnlyMode::Reject)));
+
+        assert!(matches!(match_result, Err(AppError::PostOnlyOrderWouldMatch)));
+
+        assert_eq!(to_depth(orderbook.get_asks_levels(None, None)), vec![[Decimal::from(100), Decimal::from(500)]]);
+        assert!(orderbook.get_bids_levels(None, None).is_empty());
+    }
+
+    #[test]
+    // Add post only offer limit message crossing the bid side. Order repriced behind best bid
+    fn order_should_repriced_for_crossing_post_only_ask_limit() {
+        let mut orderbook = new_empty_orderbook();
+
+        orderbook.put_order(new_limit_order(0, OrderSide::Bid, Decimal::new(1005, 1), Decimal::from(500))).unwrap();
+
+        let match_result = orderbook.put_order(new_limit_order(1, OrderSide::Ask, Decimal::from(90), Decimal::from(500)).with_post_only(Some(PostOnlyMode::Reprice))).unwrap();
+
+        assert!(match_result.trades.is_empty());
+
+        assert_eq!(to_depth(orderbook.get_asks_levels(None, None)), vec![[Decimal::new(1006, 1), Decimal::from(500)]]);
+        assert_eq!(to_depth(orderbook.get_bids_levels(None, None)), vec![[Decimal::new(1005, 1), Decimal::from(500)]]);
+    }
+
+    #[test]
+    // Add bid stop limit message. Order is released into orderbook after last trade price crosses stop price
+    fn stop_order_should_triggered_after_last_trade_price_crosses() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 1, QUOTE_ASSET_ID, Decimal::from(10000));
+        deposit(&balance_service, 2, BASE_ASSET_ID, Decimal::from(100));
+        deposit(&balance_service, 3, QUOTE_ASSET_ID, Decimal::from(10000));
+
+        market.process_new_order(new_context(), NewOrder { stop_price: Some(Decimal::from(105)), ..new_limit_order_input(1, OrderSide::Bid, Decimal::from(110), Decimal::from(10)) }).unwrap();
+
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).frozen, Decimal::from(1100));
+        assert!(market.get_orderbook_state(None, None).bids.is_empty());
+
+        market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Ask, Decimal::from(105), Decimal::from(20))).unwrap();
+        market.process_new_order(new_context(), new_limit_order_input(3, OrderSide::Bid, Decimal::from(105), Decimal::from(5))).unwrap();
+
+        assert_eq!(to_depth(market.get_orderbook_state(None, None).asks), vec![[Decimal::from(105), Decimal::from(5)]]);
+        assert_eq!(balance_service.get_balance_status(1, BASE_ASSET_ID).available, Decimal::from(10));
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).frozen, Decimal::from(0));
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(8950));
+    }
+
+    #[test]
+    // Match against a maker whose frozen balance is gone. Order still accepted with its trade since the book has already matched
+    fn market_should_return_matched_order_when_settlement_fails() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 1, QUOTE_ASSET_ID, Decimal::from(10000));
+        deposit(&balance_service, 2, BASE_ASSET_ID, Decimal::from(10));
+
+        market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Ask, Decimal::from(100), Decimal::from(10))).unwrap();
+        balance_service.change_balance(new_context(), 2, BASE_ASSET_ID, BusinessType::Withdraw, 1, BalanceType::Frozen, Decimal::from(-10)).unwrap();
+
+        let match_result = market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Bid, Decimal::from(100), Decimal::from(5))).unwrap();
+
+        assert_eq!(match_result.trades.len(), 1);
+        assert!(match_result.taker_order.is_closed());
+    }
+
+    #[test]
+    // Trigger stop order whose reservation is gone from frozen balance. Stop order rejected with an event and the triggering order still accepted
+    fn stop_order_should_rejected_when_reservation_is_missing() {
+        let notification_service = Arc::new(NotificationService::new());
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone()).with_notifications(notification_service.clone());
+
+        deposit(&balance_service, 1, QUOTE_ASSET_ID, Decimal::from(10000));
+        deposit(&balance_service, 2, BASE_ASSET_ID, Decimal::from(100));
+        deposit(&balance_service, 3, QUOTE_ASSET_ID, Decimal::from(10000));
+
+        let stop_order = market.process_new_order(new_context(), NewOrder { stop_price: Some(Decimal::from(105)), ..new_limit_order_input(1, OrderSide::Bid, Decimal::from(110), Decimal::from(10)) }).unwrap().taker_order;
+        balance_service.change_balance(new_context(), 1, QUOTE_ASSET_ID, BusinessType::Withdraw, 1, BalanceType::Frozen, Decimal::from(-1100)).unwrap();
+
+        let mut receiver = notification_service.subscribe(1);
+
+        market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Ask, Decimal::from(105), Decimal::from(20))).unwrap();
+
+        assert_eq!(market.process_new_order(new_context(), new_limit_order_input(3, OrderSide::Bid, Decimal::from(105), Decimal::from(5))).unwrap().trades.len(), 1);
+        assert_eq!(drain_user_events(&mut receiver).0, vec![OrderEventType::Rejected(AppError::UserBalanceExceeds.to_string())]);
+        assert_eq!(balance_service.get_balance_status(3, BASE_ASSET_ID).available, Decimal::from(5));
+        assert!(matches!(market.cancel_order(new_context(), 1, stop_order.get_id()), Err(AppError::OrderIdNotFound)));
+    }
+
+    #[test]
+    // Cancel pending stop order. Reserved balance released
+    fn cancel_order_for_pending_stop_should_release_reserved_balance() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(10));
+
+        market.process_new_order(new_context(), NewOrder { stop_price: Some(Decimal::from(95)), ..new_limit_order_input(1, OrderSide::Ask, Decimal::from(90), Decimal::from(10)) }).unwrap();
+
+        assert_eq!(balance_service.get_balance_status(1, BASE_ASSET_ID).frozen, Decimal::from(10));
+
+        let cancelled_order = market.cancel_order(new_context(), 1, 1).unwrap();
+
+        assert_eq!(cancelled_order.get_status(), OrderStatus::Cancelled);
+        assert_eq!(balance_service.get_balance_status(1, BASE_ASSET_ID).available, Decimal::from(10));
+    }
+
+    #[test]
+    // Match bid limit with offer limit. Maker and taker fees charged and maker rebate paid by fee collector
+    fn trade_should_charge_maker_and_taker_fees() {
+        let balance_service = new_balance_service();
+        let market_config = MarketConfig { maker_fee_rate: Decimal::new(-1, 3), taker_fee_rate: Decimal::new(2, 3), fee_collector_user_id: 9, ..new_market_config() };
+        let mut market = Market::new(&market_config, balance_service.clone(), Arc::new(FeeService::new(vec![])), Arc::new(Sequencer::new()), Arc::new(Sequencer::new()));
+
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(10));
+        deposit(&balance_service, 2, QUOTE_ASSET_ID, Decimal::from(1000));
+        deposit(&balance_service, 9, QUOTE_ASSET_ID, Decimal::from(10));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(10))).unwrap();
+        market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Bid, Decimal::from(100), Decimal::from(10))).unwrap();
+
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(1001));
+        assert_eq!(balance_service.get_balance_status(2, BASE_ASSET_ID).available, Decimal::new(998, 2));
+        assert_eq!(balance_service.get_balance_status(9, BASE_ASSET_ID).available, Decimal::new(2, 2));
+        assert_eq!(balance_service.get_balance_status(9, QUOTE_ASSET_ID).available, Decimal::from(9));
+    }
+
+    #[test]
+    // Match with a maker rebate while the fee collector is unfunded or short. Rebate capped at collector funds and the trade settles fully
+    fn trade_should_cap_maker_rebate_at_fee_collector_balance() {
+        let balance_service = new_balance_service();
+        let market_config = MarketConfig { maker_fee_rate: Decimal::new(-1, 3), taker_fee_rate: Decimal::new(2, 3), fee_collector_user_id: 9, ..new_market_config() };
+        let mut market = Market::new(&market_config, balance_service.clone(), Arc::new(FeeService::new(vec![])), Arc::new(Sequencer::new()), Arc::new(Sequencer::new()));
+
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(20));
+        deposit(&balance_service, 2, QUOTE_ASSET_ID, Decimal::from(2000));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(10))).unwrap();
+        let match_result = market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Bid, Decimal::from(100), Decimal::from(10))).unwrap();
+
+        assert_eq!(match_result.trades[0].get_maker_fee(), Decimal::ZERO);
+        assert!(market.get_orderbook_state(None, None).asks.is_empty());
+        assert_eq!(balance_service.get_balance_status(1, BASE_ASSET_ID).frozen, Decimal::ZERO);
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(1000));
+        assert_eq!(balance_service.get_balance_status(2, BASE_ASSET_ID).available, Decimal::new(998, 2));
+
+        deposit(&balance_service, 9, QUOTE_ASSET_ID, Decimal::new(5, 1));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(10))).unwrap();
+        let match_result = market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Bid, Decimal::from(100), Decimal::from(10))).unwrap();
+
+        assert_eq!(match_result.trades[0].get_maker_fee(), Decimal::new(-5, 1));
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::new(20005, 1));
+        assert_eq!(balance_service.get_balance_status(9, QUOTE_ASSET_ID).available, Decimal::ZERO);
+    }
+
+    #[test]
+    // Fee tier selected from rolling volume in the quote asset unless user has an explicit override. Tier rates only apply where lower than market rates
+    fn fee_service_should_select_tier_by_rolling_volume_and_override() {
+        let default_rates = FeeRates { maker_fee_rate: Decimal::new(1, 3), taker_fee_rate: Decimal::new(2, 3) };
+        let vip_tier = FeeTierConfig { level: 2, min_volume: Decimal::from(1000), maker_fee_rate: Decimal::from(0), taker_fee_rate: Decimal::new(1, 3) };
+        let fee_service = FeeService::new(vec![vip_tier]);
+        let now = Time::get_current_timestamp();
+
+        assert_eq!(fee_service.get_effective_rates(1, QUOTE_ASSET_ID, default_rates, now), default_rates);
+
+        fee_service.record_volume(1, QUOTE_ASSET_ID, Decimal::from(1500), now);
+
+        let fee_status = fee_service.get_user_fee_status(1, QUOTE_ASSET_ID, default_rates, now);
+
+        assert_eq!(fee_status.tier_level, Some(2));
+        assert_eq!(fee_status.rates, FeeRates { maker_fee_rate: Decimal::from(0), taker_fee_rate: Decimal::new(1, 3) });
+
+        let cheap_market_rates = FeeRates { maker_fee_rate: Decimal::new(-2, 4), taker_fee_rate: Decimal::new(5, 4) };
+
+        assert_eq!(fee_service.get_effective_rates(1, QUOTE_ASSET_ID, cheap_market_rates, now), cheap_market_rates);
+        assert_eq!(fee_service.get_effective_rates(1, QUOTE_ASSET_ID, FeeRates { taker_fee_rate: Decimal::new(3, 3), ..cheap_market_rates }, now), FeeRates { maker_fee_rate: Decimal::new(-2, 4), taker_fee_rate: Decimal::new(1, 3) });
+
+        fee_service.record_volume(2, QUOTE_ASSET_ID, Decimal::from(1500), now - 31 * 24 * 60 * 60 * 1000);
+
+        assert_eq!(fee_service.get_effective_rates(2, QUOTE_ASSET_ID, default_rates, now), default_rates);
+
+        fee_service.record_volume(3, BASE_ASSET_ID, Decimal::from(1500), now);
+
+        assert_eq!(fee_service.get_effective_rates(3, QUOTE_ASSET_ID, default_rates, now), default_rates);
+
+        let override_rates = FeeRates { maker_fee_rate: Decimal::new(-1, 4), taker_fee_rate: Decimal::new(5, 4) };
+        fee_service.set_override(2, Some(override_rates));
+
+        assert_eq!(fee_service.get_effective_rates(2, QUOTE_ASSET_ID, default_rates, now), override_rates);
+        assert_eq!(parse_fee_rate("maker_fee_rate", "-0.0001").unwrap(), Decimal::new(-1, 4));
+        assert!(matches!(parse_fee_rate("maker_fee_rate", "-1"), Err(AppError::InvalidRequestField(_, _))));
+        assert!(matches!(parse_fee_rate("taker_fee_rate", "1"), Err(AppError::InvalidRequestField(_, _))));
+        assert!(matches!(parse_taker_fee_rate("taker_fee_rate", "-0.0001"), Err(AppError::InvalidRequestField(_, _))));
+        assert_eq!(parse_taker_fee_rate("taker_fee_rate", "0.001").unwrap(), Decimal::new(1, 3));
+    }
+
+    #[test]
+    // Add limit messages violating market tick size, lot size and notional limits. Orders rejected
+    fn order_should_rejected_for_invalid_tick_step_and_notional() {
+        let balance_service = new_balance_service();
+        let market_config = MarketConfig { price_tick: Some(Decimal::new(1, 2)), quantity_step: Some(Decimal::new(1, 1)), max_quantity: Some(Decimal::from(100)), min_notional: Some(Decimal::from(10)), max_notional: Some(Decimal::from(5000)), ..new_market_config() };
+        let mut market = Market::new(&market_config, balance_service.clone(), Arc::new(FeeService::new(vec![])), Arc::new(Sequencer::new()), Arc::new(Sequencer::new()));
+
+        deposit(&balance_service, 1, QUOTE_ASSET_ID, Decimal::from(100000));
+
+        assert!(matches!(market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Bid, Decimal::new(1000000001, 7), Decimal::from(1))), Err(AppError::OrderPriceTickMismatch)));
+        assert!(matches!(market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Bid, Decimal::from(100), Decimal::new(105, 2))), Err(AppError::OrderQuantityStepMismatch)));
+        assert!(matches!(market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Bid, Decimal::from(1), Decimal::from(101))), Err(AppError::MarketMaximumAllowedQuantityExceeds)));
+        assert!(matches!(market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Bid, Decimal::from(1), Decimal::from(5))), Err(AppError::MarketMinimumNotionalExceeds)));
+        assert!(matches!(market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Bid, Decimal::from(100), Decimal::from(60))), Err(AppError::MarketMaximumNotionalExceeds)));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Bid, Decimal::new(10001, 2), Decimal::new(15, 1))).unwrap();
+
+        assert_eq!(to_depth(market.get_orderbook_state(None, None).bids), vec![[Decimal::new(10001, 2), Decimal::new(15, 1)]]);
+    }
+
+    #[test]
+    // Grow a balance past the largest decimal, directly and through a transfer. Change rejected and balance kept
+    fn balance_should_reject_change_that_overflows() {
+        let balance_service = new_balance_service();
+
+        deposit(&balance_service, 1, QUOTE_ASSET_ID, Decimal::MAX);
+        deposit(&balance_service, 2, QUOTE_ASSET_ID, Decimal::from(10));
+
+        assert!(matches!(balance_service.change_balance(new_context(), 1, QUOTE_ASSET_ID, BusinessType::Deposit, 2, BalanceType::Frozen, Decimal::ONE), Err(AppError::BalanceOverflow)));
+        assert!(matches!(balance_service.change_balances(new_context(), BusinessType::Trade, 1, &[BalanceDelta { user_id: 2, asset_id: QUOTE_ASSET_ID, type_: BalanceType::Available, amount: Decimal::from(-10) }, BalanceDelta { user_id: 1, asset_id: QUOTE_ASSET_ID, type_: BalanceType::Available, amount: Decimal::from(10) }]), Err(AppError::BalanceOverflow)));
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).total, Decimal::MAX);
+        assert_eq!(balance_service.get_balance_status(2, QUOTE_ASSET_ID).available, Decimal::from(10));
+    }
+
+    #[test]
+    // Place an order whose amount doesn't fit a decimal. Order rejected without stopping the market
+    fn market_should_reject_order_with_overflowing_amount() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 1, QUOTE_ASSET_ID, Decimal::from(1000));
+
+        assert!(matches!(market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Bid, Decimal::from(2), Decimal::MAX)), Err(AppError::OrderAmountOverflow)));
+        assert!(matches!(market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(2), Decimal::MAX)), Err(AppError::OrderAmountOverflow)));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Bid, Decimal::from(10), Decimal::from(5))).unwrap();
+
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).frozen, Decimal::from(50));
+    }
+
+    #[test]
+    // Restart engine with an existing journal. Balances and orderbook rebuilt by replaying commands
+    fn container_should_rebuild_state_from_journal() {
+        let journal_path = std::env::temp_dir().join(format!("match-engine-journal-{}.log", Time::get_current_timestamp()));
+        let new_config = || Config { markets: vec![new_market_config()], fee_tiers: vec![], journal: Some(JournalConfig { path: journal_path.to_str().unwrap().to_string() }), snapshot: None, ledger: None, api_keys: vec![] };
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 1, Decimal::from(1000)).unwrap();
+        container.engine_service.deposit(2, BASE_ASSET_ID, 2, Decimal::from(10)).unwrap();
+        block_on(container.engine_service.place_order(1, new_limit_order_input(1, OrderSide::Bid, Decimal::from(50), Decimal::from(10)))).unwrap();
+        block_on(container.engine_service.place_order(1, new_limit_order_input(2, OrderSide::Ask, Decimal::from(50), Decimal::from(4)))).unwrap();
+        block_on(container.engine_service.place_order(1, new_limit_order_input(1, OrderSide::Bid, Decimal::from(40), Decimal::from(5)))).unwrap();
+        block_on(container.engine_service.cancel_order(1, 1, 3)).unwrap();
+
+        drop(container);
+
+        let container = Container::new(&new_config());
+
+        std::fs::remove_file(&journal_path).unwrap();
+
+        assert_eq!(to_depth(block_on(container.engine_service.get_market_orderbook_state(1, None, None)).unwrap().bids), vec![[Decimal::from(50), Decimal::from(6)]]);
+        assert_eq!(container.balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(500));
+        assert_eq!(container.balance_service.get_balance_status(1, QUOTE_ASSET_ID).frozen, Decimal::from(300));
+        assert_eq!(container.balance_service.get_balance_status(1, BASE_ASSET_ID).available, Decimal::from(4));
+        assert_eq!(container.balance_service.get_balance_status(2, QUOTE_ASSET_ID).available, Decimal::from(200));
+    }
+
+    #[test]
+    // Restart engine with an existing journal some time later. Replayed balance changes keep the time their commands were journaled at
+    fn container_should_replay_journal_with_original_timestamps() {
+        let journal_path = std::env::temp_dir().join(format!("match-engine-timestamp-journal-{}.log", Time::get_current_timestamp()));
+        let new_config = || Config { markets: vec![new_market_config()], fee_tiers: vec![], journal: Some(JournalConfig { path: journal_path.to_str().unwrap().to_string() }), snapshot: None, ledger: None, api_keys: vec![] };
+        let filter = LedgerFilter { user_id: 1, asset_id: None, business_type: None, start_time: None, end_time: None, after_id: 0, limit: 100 };
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 1, Decimal::from(1000)).unwrap();
+        container.engine_service.deposit(2, BASE_ASSET_ID, 2, Decimal::from(10)).unwrap();
+        block_on(container.engine_service.place_order(1, new_limit_order_input(1, OrderSide::Bid, Decimal::from(50), Decimal::from(10)))).unwrap();
+        block_on(container.engine_service.place_order(1, new_limit_order_input(2, OrderSide::Ask, Decimal::from(50), Decimal::from(4)))).unwrap();
+
+        let entries = container.ledger_service.query(&filter).unwrap();
+
+        drop(container);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        let container = Container::new(&new_config());
+
+        std::fs::remove_file(&journal_path).unwrap();
+
+        let replayed_entries = container.ledger_service.query(&filter).unwrap();
+
+        assert!(entries.iter().any(|entry| entry.change.business_type == BusinessType::Trade));
+        assert_eq!(replayed_entries.iter().map(|entry| (entry.change.business_type, entry.change.created_at)).collect::<Vec<_>>(), entries.iter().map(|entry| (entry.change.business_type, entry.change.created_at)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    // Restart engine with a fee override in the journal. Override is replayed before the trades it priced, so balances come out the same
+    fn container_should_replay_fee_override_from_journal() {
+        let journal_path = std::env::temp_dir().join(format!("match-engine-fee-journal-{}.log", Time::get_current_timestamp()));
+        let new_config = || Config { markets: vec![new_market_config()], fee_tiers: vec![], journal: Some(JournalConfig { path: journal_path.to_str().unwrap().to_string() }), snapshot: None, ledger: None, api_keys: vec![] };
+        let override_rates = FeeRates { maker_fee_rate: Decimal::ZERO, taker_fee_rate: Decimal::new(1, 2) };
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 1, Decimal::from(1000)).unwrap();
+        container.engine_service.deposit(2, BASE_ASSET_ID, 2, Decimal::from(10)).unwrap();
+        container.engine_service.set_fee_override(2, Some(override_rates)).unwrap();
+        block_on(container.engine_service.place_order(1, new_limit_order_input(1, OrderSide::Bid, Decimal::from(50), Decimal::from(10)))).unwrap();
+        block_on(container.engine_service.place_order(1, new_limit_order_input(2, OrderSide::Ask, Decimal::from(50), Decimal::from(4)))).unwrap();
+
+        assert_eq!(container.balance_service.get_balance_status(2, QUOTE_ASSET_ID).available, Decimal::from(198));
+
+        drop(container);
+
+        let container = Container::new(&new_config());
+
+        std::fs::remove_file(&journal_path).unwrap();
+
+        assert_eq!(container.fee_service.get_effective_rates(2, QUOTE_ASSET_ID, FeeRates { maker_fee_rate: Decimal::ZERO, taker_fee_rate: Decimal::ZERO }, Time::get_current_timestamp()), override_rates);
+        assert_eq!(container.balance_service.get_balance_status(2, QUOTE_ASSET_ID).available, Decimal::from(198));
+        assert_eq!(container.balance_service.get_balance_status(0, QUOTE_ASSET_ID).available, Decimal::from(2));
+    }
+
+    #[test]
+    // Restart engine after a crash tore the last journal line. Torn line is cut off and commands journaled after the restart survive the next one
+    fn container_should_recover_journal_after_torn_append() {
+        let journal_path = std::env::temp_dir().join(format!("match-engine-torn-journal-{}.log", Time::get_current_timestamp()));
+        let new_config = || Config { markets: vec![new_market_config()], fee_tiers: vec![], journal: Some(JournalConfig { path: journal_path.to_str().unwrap().to_string() }), snapshot: None, ledger: None, api_keys: vec![] };
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 1, Decimal::from(1000)).unwrap();
+
+        drop(container);
+
+        std::fs::OpenOptions::new().append(true).open(&journal_path).unwrap().write_all(b"{\"sequence\":2,\"created_at").unwrap();
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 2, Decimal::from(500)).unwrap();
+
+        drop(container);
+
+        let container = Container::new(&new_config());
+
+        std::fs::remove_file(&journal_path).unwrap();
+
+        assert_eq!(container.balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(1500));
+    }
+
+    #[test]
+    // Send commands that can't apply, then restart with a journaled command that fails. Rejected commands never journaled and replay carries on past the failing one
+    fn container_should_journal_only_valid_commands_and_replay_past_failing_ones() {
+        let journal_path = std::env::temp_dir().join(format!("match-engine-rejected-journal-{}.log", Time::get_current_timestamp()));
+        let new_config = || Config { markets: vec![new_market_config()], fee_tiers: vec![], journal: Some(JournalConfig { path: journal_path.to_str().unwrap().to_string() }), snapshot: None, ledger: None, api_keys: vec![] };
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 1, Decimal::from(1000)).unwrap();
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 1, Decimal::from(1000)).unwrap();
+
+        assert!(matches!(container.engine_service.deposit(1, QUOTE_ASSET_ID, 1, Decimal::from(999)), Err(AppError::BusinessIdConflict)));
+        assert!(matches!(container.engine_service.withdraw(1, QUOTE_ASSET_ID, 2, Decimal::from(5000)), Err(AppError::UserBalanceExceeds)));
+        assert!(matches!(block_on(container.engine_service.place_order(1, new_limit_order_input(1, OrderSide::Bid, Decimal::from(50), Decimal::from(100)))), Err(AppError::UserBalanceExceeds)));
+        assert!(matches!(block_on(container.engine_service.cancel_order(1, 1, 1)), Err(AppError::OrderIdNotFound)));
+        assert_eq!(std::fs::read_to_string(&journal_path).unwrap().lines().count(), 1);
+
+        drop(container);
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&journal_path).unwrap();
+
+        for entry in [JournalEntry { sequence: 2, created_at: Time::get_current_timestamp(), command: Command::Withdraw { user_id: 1, asset_id: QUOTE_ASSET_ID, business_id: 2, amount: Decimal::from(5000) } }, JournalEntry { sequence: 3, created_at: Time::get_current_timestamp(), command: Command::Deposit { user_id: 1, asset_id: QUOTE_ASSET_ID, business_id: 3, amount: Decimal::from(500) } }] {
+            file.write_all(format!("{}\n", serde_json::to_string(&entry).unwrap()).as_bytes()).unwrap();
+        }
+
+        drop(file);
+
+        let container = Container::new(&new_config());
+
+        std::fs::remove_file(&journal_path).unwrap();
+
+        assert_eq!(container.balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(1500));
+        assert_eq!(block_on(container.engine_service.place_order(1, new_limit_order_input(1, OrderSide::Bid, Decimal::from(50), Decimal::from(10)))).unwrap().taker_order.get_id(), 1);
+    }
+
+    #[test]
+    // Restart engine from the latest snapshot. Only journal commands after the snapshot are replayed on top of it
+    fn container_should_restore_snapshot_and_replay_journal_tail() {
+        let timestamp = Time::get_current_timestamp();
+        let journal_path = std::env::temp_dir().join(format!("match-engine-snapshot-journal-{}.log", timestamp));
+        let snapshot_path = std::env::temp_dir().join(format!("match-engine-snapshots-{}", timestamp));
+        let new_config = || Config { markets: vec![new_market_config()], fee_tiers: vec![], journal: Some(JournalConfig { path: journal_path.to_str().unwrap().to_string() }), snapshot: Some(SnapshotConfig { path: snapshot_path.to_str().unwrap().to_string(), interval_secs: None, retain_count: None }), ledger: None, api_keys: vec![] };
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 1, Decimal::from(1000)).unwrap();
+        container.engine_service.deposit(2, BASE_ASSET_ID, 2, Decimal::from(10)).unwrap();
+        block_on(container.engine_service.place_order(1, new_limit_order_input(1, OrderSide::Bid, Decimal::from(50), Decimal::from(10)))).unwrap();
+
+        assert_eq!(container.snapshot_service.as_ref().unwrap().take_snapshot().unwrap(), 3);
+
+        block_on(container.engine_service.place_order(1, new_limit_order_input(2, OrderSide::Ask, Decimal::from(50), Decimal::from(4)))).unwrap();
+        block_on(container.engine_service.place_order(1, new_limit_order_input(1, OrderSide::Bid, Decimal::from(40), Decimal::from(5)))).unwrap();
+
+        drop(container);
+
+        let container = Container::new(&new_config());
+
+        block_on(container.engine_service.cancel_order(1, 1, 3)).unwrap();
+
+        std::fs::remove_file(&journal_path).unwrap();
+        std::fs::remove_dir_all(&snapshot_path).unwrap();
+
+        assert_eq!(to_depth(block_on(container.engine_service.get_market_orderbook_state(1, None, None)).unwrap().bids), vec![[Decimal::from(50), Decimal::from(6)]]);
+        assert_eq!(container.balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(500));
+        assert_eq!(container.balance_service.get_balance_status(1, QUOTE_ASSET_ID).frozen, Decimal::from(300));
+        assert_eq!(container.balance_service.get_balance_status(1, BASE_ASSET_ID).available, Decimal::from(4));
+        assert_eq!(container.balance_service.get_balance_status(2, QUOTE_ASSET_ID).available, Decimal::from(200));
+    }
+
+    #[test]
+    // Trade on two markets, then restart from a snapshot and trade again. Trade ids never repeat across markets or restarts
+    fn markets_should_share_trade_ids_across_markets_and_snapshots() {
+        let snapshot_path = std::env::temp_dir().join(format!("match-engine-trade-id-snapshots-{}", Time::get_current_timestamp()));
+        let new_config = || Config { markets: vec![new_market_config(), MarketConfig { pair_id: 2, ..new_market_config() }], fee_tiers: vec![], journal: None, snapshot: Some(SnapshotConfig { path: snapshot_path.to_str().unwrap().to_string(), interval_secs: None, retain_count: None }), ledger: None, api_keys: vec![] };
+        let trade = |container: &Container, pair_id: PairId| {
+            block_on(container.engine_service.place_order(pair_id, new_limit_order_input(2, OrderSide::Ask, Decimal::from(50), Decimal::from(1)))).unwrap();
+            block_on(container.engine_service.place_order(pair_id, new_limit_order_input(1, OrderSide::Bid, Decimal::from(50), Decimal::from(1)))).unwrap().trades[0].get_id()
+        };
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 1, Decimal::from(1000)).unwrap();
+        container.engine_service.deposit(2, BASE_ASSET_ID, 2, Decimal::from(10)).unwrap();
+
+        assert_eq!([trade(&container, 1), trade(&container, 2)], [1, 2]);
+
+        container.snapshot_service.as_ref().unwrap().take_snapshot().unwrap();
+
+        drop(container);
+
+        let container = Container::new(&new_config());
+
+        std::fs::remove_dir_all(&snapshot_path).unwrap();
+
+        assert_eq!([trade(&container, 2), trade(&container, 1)], [3, 4]);
+    }
+
+    #[test]
+    // Take more snapshots than are retained. Oldest snapshot files are removed and the journal only keeps commands after the oldest retained one
+    fn snapshot_should_prune_old_snapshots_and_truncate_journal() {
+        let timestamp = Time::get_current_timestamp();
+        let journal_path = std::env::temp_dir().join(format!("match-engine-pruned-journal-{}.log", timestamp));
+        let snapshot_path = std::env::temp_dir().join(format!("match-engine-pruned-snapshots-{}", timestamp));
+        let new_config = || Config { markets: vec![new_market_config()], fee_tiers: vec![], journal: Some(JournalConfig { path: journal_path.to_str().unwrap().to_string() }), snapshot: Some(SnapshotConfig { path: snapshot_path.to_str().unwrap().to_string(), interval_secs: None, retain_count: Some(2) }), ledger: None, api_keys: vec![] };
+
+        let container = Container::new(&new_config());
+
+        for business_id in 1..=3 {
+            container.engine_service.deposit(1, QUOTE_ASSET_ID, business_id, Decimal::from(100)).unwrap();
+            container.snapshot_service.as_ref().unwrap().take_snapshot().unwrap();
+        }
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 4, Decimal::from(100)).unwrap();
+
+        let mut snapshot_files = std::fs::read_dir(&snapshot_path).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().to_string()).collect::<Vec<String>>();
+        snapshot_files.sort();
+
+        assert_eq!(snapshot_files, vec![format!("snapshot-{:020}.json", 2), format!("snapshot-{:020}.json", 3)]);
+        assert_eq!(std::fs::read_to_string(&journal_path).unwrap().lines().count(), 2);
+
+        drop(container);
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 5, Decimal::from(100)).unwrap();
+
+        std::fs::remove_file(&journal_path).unwrap();
+        std::fs::remove_dir_all(&snapshot_path).unwrap();
+
+        assert_eq!(container.balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(500));
+    }
+
+    #[test]
+    // Convert app errors and invalid request fields to gRPC status. Codes and error details match the error kind
+    fn app_errors_should_map_to_grpc_status_codes() {
+        assert_eq!(tonic::Status::from(AppError::UserBalanceExceeds).code(), tonic::Code::ResourceExhausted);
+        assert_eq!(tonic::Status::from(AppError::MarketNotFound).code(), tonic::Code::NotFound);
+        assert_eq!(tonic::Status::from(AppError::OrderUserMismatch).code(), tonic::Code::PermissionDenied);
+
+        let status = tonic::Status::from(parse_positive_decimal("amount", "-1").unwrap_err());
+        let details = ErrorDetail::decode(status.details()).unwrap();
+
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(details.reason, "INVALID_REQUEST_FIELD");
+        assert_eq!(details.field, "amount");
+        assert!(matches!(parse_positive_decimal("amount", "abc"), Err(AppError::InvalidRequestField(_, _))));
+        assert!(matches!(parse_positive_decimal("quantity", "1000000000000"), Err(AppError::InvalidRequestField(_, _))));
+        assert!(matches!(parse_positive_decimal("quantity", "0.0000000000000000001"), Err(AppError::InvalidRequestField(_, _))));
+        assert_eq!(parse_positive_decimal("quantity", "999999999999.500000000000000000").unwrap(), Decimal::new(9999999999995, 1));
+    }
+
+    #[test]
+    // Restart engine with a file ledger and journal. Replayed commands do not duplicate entries and history pages by id
+    fn ledger_should_record_balance_changes_once_across_restarts() {
+        let timestamp = Time::get_current_timestamp();
+        let journal_path = std::env::temp_dir().join(format!("match-engine-ledger-journal-{}.log", timestamp));
+        let ledger_path = std::env::temp_dir().join(format!("match-engine-ledger-{}.log", timestamp));
+        let new_config = || Config { markets: vec![new_market_config()], fee_tiers: vec![], journal: Some(JournalConfig { path: journal_path.to_str().unwrap().to_string() }), snapshot: None, ledger: Some(LedgerConfig { path: ledger_path.to_str().unwrap().to_string() }), api_keys: vec![] };
+        let new_filter = |business_type: Option<BusinessType>, after_id: LedgerEntryId, limit: usize| LedgerFilter { user_id: 1, asset_id: Some(QUOTE_ASSET_ID), business_type, start_time: None, end_time: None, after_id, limit };
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 1, Decimal::from(1000)).unwrap();
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 2, Decimal::from(500)).unwrap();
+        block_on(container.engine_service.place_order(1, new_limit_order_input(1, OrderSide::Bid, Decimal::from(50), Decimal::from(10)))).unwrap();
+
+        drop(container);
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.withdraw(1, QUOTE_ASSET_ID, 3, Decimal::from(100)).unwrap();
+
+        std::fs::remove_file(&journal_path).unwrap();
+        std::fs::remove_file(&ledger_path).unwrap();
+
+        let entries = container.ledger_service.query(&new_filter(None, 0, 100)).unwrap();
+
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries.iter().map(|entry| entry.change.business_type).collect::<Vec<BusinessType>>(), vec![BusinessType::Deposit, BusinessType::Deposit, BusinessType::Order, BusinessType::Order, BusinessType::Withdraw]);
+        assert_eq!(entries[4].change.available_balance, Decimal::from(900));
+        assert_eq!(entries.iter().map(|entry| entry.journal_sequence).collect::<Vec<u64>>(), vec![1, 2, 3, 3, 4]);
+
+        let first_page = container.ledger_service.query(&new_filter(Some(BusinessType::Deposit), 0, 1)).unwrap();
+        let second_page = container.ledger_service.query(&new_filter(Some(BusinessType::Deposit), first_page[0].id, 1)).unwrap();
+
+        assert_eq!(first_page[0].change.business_id, 1);
+        assert_eq!(second_page[0].change.business_id, 2);
+    }
+
+    #[test]
+    // Restart engine after a crash cut a command's ledger entries short. Only the missing entries of that command are recorded again
+    fn ledger_should_complete_partially_recorded_command_on_replay() {
+        let timestamp = Time::get_current_timestamp();
+        let journal_path = std::env::temp_dir().join(format!("match-engine-partial-ledger-journal-{}.log", timestamp));
+        let ledger_path = std::env::temp_dir().join(format!("match-engine-partial-ledger-{}.log", timestamp));
+        let new_config = || Config { markets: vec![new_market_config()], fee_tiers: vec![], journal: Some(JournalConfig { path: journal_path.to_str().unwrap().to_string() }), snapshot: None, ledger: Some(LedgerConfig { path: ledger_path.to_str().unwrap().to_string() }), api_keys: vec![] };
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 1, Decimal::from(1000)).unwrap();
+        container.engine_service.deposit(2, BASE_ASSET_ID, 2, Decimal::from(10)).unwrap();
+        block_on(container.engine_service.place_order(1, new_limit_order_input(1, OrderSide::Bid, Decimal::from(50), Decimal::from(10)))).unwrap();
+        block_on(container.engine_service.place_order(1, new_limit_order_input(2, OrderSide::Ask, Decimal::from(50), Decimal::from(4)))).unwrap();
+
+        drop(container);
+
+        let ledger = std::fs::read_to_string(&ledger_path).unwrap();
+        let lines = ledger.lines().collect::<Vec<_>>();
+
+        assert!(lines[lines.len() - 2..].iter().all(|line| serde_json::from_str::<LedgerEntry>(line).unwrap().journal_sequence == 4));
+
+        std::fs::write(&ledger_path, lines[..lines.len() - 1].iter().map(|line| format!("{}\n", line)).collect::<String>()).unwrap();
+
+        drop(Container::new(&new_config()));
+
+        let replayed_ledger = std::fs::read_to_string(&ledger_path).unwrap();
+
+        std::fs::remove_file(&journal_path).unwrap();
+        std::fs::remove_file(&ledger_path).unwrap();
+
+        assert_eq!(replayed_ledger, ledger);
+    }
+
+    #[test]
+    // Restart engine after a crash tore the last ledger line. Entries recorded after the restart are still there on the next one
+    fn ledger_should_recover_after_torn_append() {
+        let ledger_path = std::env::temp_dir().join(format!("match-engine-torn-ledger-{}.log", Time::get_current_timestamp()));
+        let new_config = || Config { markets: vec![new_market_config()], fee_tiers: vec![], journal: None, snapshot: None, ledger: Some(LedgerConfig { path: ledger_path.to_str().unwrap().to_string() }), api_keys: vec![] };
+        let filter = LedgerFilter { user_id: 1, asset_id: None, business_type: None, start_time: None, end_time: None, after_id: 0, limit: 100 };
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 1, Decimal::from(1000)).unwrap();
+
+        drop(container);
+
+        std::fs::OpenOptions::new().append(true).open(&ledger_path).unwrap().write_all(b"{\"id\":2,\"journal_seq").unwrap();
+
+        let container = Container::new(&new_config());
+
+        container.engine_service.deposit(1, QUOTE_ASSET_ID, 2, Decimal::from(500)).unwrap();
+
+        drop(container);
+
+        let container = Container::new(&new_config());
+
+        std::fs::remove_file(&ledger_path).unwrap();
+
+        let entries = container.ledger_service.query(&filter).unwrap();
+
+        assert_eq!(entries.iter().map(|entry| (entry.id, entry.change.business_id)).collect::<Vec<(LedgerEntryId, u64)>>(), vec![(1, 1), (2, 2)]);
+    }
+
+    #[test]
+    // Retried deposit and withdraw with same business id, also concurrently. Original result returned and balance changed once
+    fn balance_service_should_dedupe_deposit_and_withdraw_by_business_id() {
+        let balance_service = new_balance_service();
+
+        let first_output = balance_service.deposit(new_context(), 1, QUOTE_ASSET_ID, 10, Decimal::from(100)).unwrap();
+        let retried_output = balance_service.deposit(new_context(), 1, QUOTE_ASSET_ID, 10, Decimal::from(100)).unwrap();
+
+        assert_eq!(retried_output.created_at, first_output.created_at);
+        assert_eq!(retried_output.available_balance, Decimal::from(100));
+
+        balance_service.withdraw(new_context(), 1, QUOTE_ASSET_ID, 10, Decimal::from(30)).unwrap();
+        balance_service.withdraw(new_context(), 1, QUOTE_ASSET_ID, 10, Decimal::from(30)).unwrap();
+
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(70));
+        assert!(matches!(balance_service.deposit(new_context(), 2, QUOTE_ASSET_ID, 10, Decimal::from(100)), Err(AppError::BusinessIdConflict)));
+        assert!(matches!(balance_service.withdraw(new_context(), 1, QUOTE_ASSET_ID, 11, Decimal::from(500)), Err(AppError::UserBalanceExceeds)));
+
+        balance_service.deposit(new_context(), 1, QUOTE_ASSET_ID, 11, Decimal::from(500)).unwrap();
+
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(570));
+
+        let threads: Vec<_> = (0..8).map(|_| {
+            let balance_service = balance_service.clone();
+            std::thread::spawn(move || balance_service.deposit(new_context(), 3, QUOTE_ASSET_ID, 12, Decimal::from(100)).unwrap())
+        }).collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(balance_service.get_balance_status(3, QUOTE_ASSET_ID).available, Decimal::from(100));
+    }
+
+    #[test]
+    // Book, partially match, fill and reject orders. Lifecycle and balance events reach only their owners
+    fn user_events_should_be_published_to_order_owners() {
+        let notification_service = Arc::new(NotificationService::new());
+        let balance_source: Arc<Box<dyn BalanceSourceExector>> = Arc::new(Box::new(MemoryBalanceManager::new()));
+        let balance_service = Arc::new(BalanceService::new(balance_source).with_notifications(notification_service.clone()));
+        let mut market = new_market(balance_service.clone()).with_notifications(notification_service.clone());
+
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(10));
+        balance_service.change_balance(new_context(), 2, QUOTE_ASSET_ID, BusinessType::Deposit, 2, BalanceType::Available, Decimal::from(300)).unwrap();
+
+        let mut maker_receiver = notification_service.subscribe(1);
+        let mut taker_receiver = notification_service.subscribe(2);
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(4))).unwrap();
+        market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Bid, Decimal::from(100), Decimal::from(3))).unwrap();
+
+        let (maker_order_events, maker_balance_events) = drain_user_events(&mut maker_receiver);
+        let (taker_order_events, taker_balance_events) = drain_user_events(&mut taker_receiver);
+
+        assert_eq!(maker_order_events, vec![OrderEventType::Accepted, OrderEventType::PartiallyFilled]);
+        assert_eq!(taker_order_events, vec![OrderEventType::Accepted, OrderEventType::Filled]);
+        assert!(maker_balance_events > 0);
+        assert!(taker_balance_events > 0);
+
+        assert!(market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Bid, Decimal::from(100), Decimal::from(1))).is_err());
+
+        let (taker_order_events, taker_balance_events) = drain_user_events(&mut taker_receiver);
+
+        assert_eq!(taker_order_events, vec![OrderEventType::Rejected(AppError::UserBalanceExceeds.to_string())]);
+        assert_eq!(taker_balance_events, 0);
+        assert!(maker_receiver.try_recv().is_err());
+
+        market.cancel_order(new_context(), 1, 1).unwrap();
+
+        let (maker_order_events, _) = drain_user_events(&mut maker_receiver);
+
+        assert_eq!(maker_order_events, vec![OrderEventType::Cancelled]);
+    }
+
+    #[test]
+    // Authenticate requests by bearer api key. Missing or unknown keys are rejected and admin calls need an admin key
+    fn requests_should_be_authenticated_by_api_key() {
+        let authenticator = Authenticator::new(&[ApiKeyConfig { user_id: 7, key: "secret".to_string(), is_admin: false }, ApiKeyConfig { user_id: 1, key: "admin-secret".to_string(), is_admin: true }]);
+        let new_request = |value: Option<&str>| {
+            let mut request = Request::new(());
+            if let Some(value) = value {
+                request.metadata_mut().insert("authorization", value.parse().unwrap());
+            }
+            request
+        };
+
+        assert_eq!(authenticator.authenticate(&new_request(Some("Bearer secret"))).unwrap(), 7);
+        assert!(matches!(authenticator.authenticate(&new_request(Some("Bearer wrong"))), Err(AppError::Unauthenticated)));
+        assert!(matches!(authenticator.authenticate(&new_request(Some("secret"))), Err(AppError::Unauthenticated)));
+        assert!(matches!(authenticator.authenticate(&new_request(None)), Err(AppError::Unauthenticated)));
+        assert_eq!(authenticator.authenticate_admin(&new_request(Some("Bearer admin-secret"))).unwrap(), 1);
+        assert!(matches!(authenticator.authenticate_admin(&new_request(Some("Bearer secret"))), Err(AppError::AdminRequired)));
+        assert!(matches!(authenticator.authenticate_admin(&new_request(None)), Err(AppError::Unauthenticated)));
+    }
+
+    #[test]
+    // Record trades into every interval. Bars aggregate OHLCV and late trades land in their own bar
+    fn candles_should_aggregate_trades_per_interval() {
+        let candle_service = CandleService::new();
+        let mut receiver = candle_service.subscribe(1, CandleInterval::OneMinute);
+        let new_trade = |price: u32, quantity: u32, created_at: u64| TradeEvent { pair_id: 1, trade_id: 0, price: Decimal::from(price), quantity: Decimal::from(quantity), taker_side: OrderSide::Bid, created_at };
+
+        candle_service.record_trade(&new_trade(100, 1, 60_000));
+        candle_service.record_trade(&new_trade(120, 2, 90_000));
+        candle_service.record_trade(&new_trade(90, 1, 119_999));
+        candle_service.record_trade(&new_trade(110, 3, 180_000));
+        candle_service.record_trade(&new_trade(95, 1, 130_000));
+
+        let candles = candle_service.get_candles(1, CandleInterval::OneMinute, 0, u64::MAX);
+
+        assert_eq!(candles.iter().map(|candle| candle.open_time).collect::<Vec<_>>(), vec![60_000, 120_000, 180_000]);
+        assert_eq!((candles[0].open, candles[0].high, candles[0].low, candles[0].close), (Decimal::from(100), Decimal::from(120), Decimal::from(90), Decimal::from(90)));
+        assert_eq!(candles[0].volume, Decimal::from(4));
+        assert_eq!(candles[0].quote_volume, Decimal::from(430));
+        assert_eq!(candles[0].trade_count, 3);
+        assert_eq!(candles[0].get_close_time(CandleInterval::OneMinute), 119_999);
+        assert_eq!(candles[1].close, Decimal::from(95));
+
+        assert_eq!(candle_service.get_candles(1, CandleInterval::OneMinute, 100_000, 180_000).len(), 2);
+        assert!(candle_service.get_candles(2, CandleInterval::OneMinute, 0, u64::MAX).is_empty());
+
+        let candles = candle_service.get_candles(1, CandleInterval::FiveMinutes, 0, u64::MAX);
+
+        assert_eq!(candles.len(), 1);
+        assert_eq!((candles[0].open, candles[0].close, candles[0].trade_count), (Decimal::from(100), Decimal::from(95), 5));
+
+        assert_eq!(receiver.try_recv().unwrap().trade_count, 1);
+        assert_eq!(receiver.try_recv().unwrap().trade_count, 2);
+    }
+
+    #[test]
+    // Match offer limits in a market. Executed trades feed candles
+    fn market_trades_should_feed_candles() {
+        let balance_service = new_balance_service();
+        let candle_service = Arc::new(CandleService::new());
+        let mut market = new_market(balance_service.clone()).with_candles(candle_service.clone());
+
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(10));
+        balance_service.change_balance(new_context(), 2, QUOTE_ASSET_ID, BusinessType::Deposit, 2, BalanceType::Available, Decimal::from(1000)).unwrap();
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(2))).unwrap();
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(110), Decimal::from(2))).unwrap();
+        market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Bid, Decimal::from(110), Decimal::from(3))).unwrap();
+
+        for interval in CandleInterval::ALL {
+            let candles = candle_service.get_candles(1, interval, 0, u64::MAX);
+
+            assert_eq!(candles.len(), 1);
+            assert_eq!((candles[0].open, candles[0].high, candles[0].low, candles[0].close), (Decimal::from(100), Decimal::from(110), Decimal::from(100), Decimal::from(110)));
+            assert_eq!(candles[0].volume, Decimal::from(3));
+            assert_eq!(candles[0].trade_count, 2);
+        }
+    }
+
+    #[test]
+    // Match offer limits and keep both sides booked. Ticker reports last price, book tops and 24h window stats
+    fn ticker_should_report_rolling_window_and_book_tops() {
+        let balance_service = new_balance_service();
+        let candle_service = Arc::new(CandleService::new());
+        let mut market = new_market(balance_service.clone()).with_candles(candle_service.clone());
+
+        let ticker = market.get_ticker(Time::get_current_timestamp());
+
+        assert!(ticker.last_price.is_none() && ticker.best_bid.is_none() && ticker.best_ask.is_none() && ticker.open_price.is_none());
+        assert_eq!(ticker.trade_count, 0);
+
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(10));
+        balance_service.change_balance(new_context(), 2, QUOTE_ASSET_ID, BusinessType::Deposit, 2, BalanceType::Available, Decimal::from(1000)).unwrap();
+
+        let now = Time::get_current_timestamp();
+
+        candle_service.record_trade(&TradeEvent { pair_id: 1, trade_id: 0, price: Decimal::from(50), quantity: Decimal::from(1), taker_side: OrderSide::Bid, created_at: now - 2 * 24 * 60 * 60 * 1000 });
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(2))).unwrap();
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(125), Decimal::from(3))).unwrap();
+        market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Bid, Decimal::from(125), Decimal::from(3))).unwrap();
+        market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Bid, Decimal::from(90), Decimal::from(4))).unwrap();
+
+        let ticker = market.get_ticker(Time::get_current_timestamp());
+
+        assert_eq!(ticker.last_price, Some(Decimal::from(125)));
+        assert_eq!(ticker.best_bid, Some(DepthLevel { price: Decimal::from(90), quantity: Decimal::from(4), order_count: 1 }));
+        assert_eq!(ticker.best_ask, Some(DepthLevel { price: Decimal::from(125), quantity: Decimal::from(2), order_count: 1 }));
+        assert_eq!((ticker.open_price, ticker.high_price, ticker.low_price), (Some(Decimal::from(100)), Some(Decimal::from(125)), Some(Decimal::from(100))));
+        assert_eq!(ticker.volume, Decimal::from(3));
+        assert_eq!(ticker.quote_volume, Decimal::from(325));
+        assert_eq!(ticker.trade_count, 2);
+        assert_eq!(ticker.price_change, Some(Decimal::from(25)));
+        assert_eq!(ticker.price_change_percent, Some(Decimal::from(25)));
+        assert_eq!(ticker.close_time - ticker.open_time + 1, 24 * 60 * 60 * 1000);
+    }
+
+    #[test]
+    // Place orders on two markets from many tasks at once. Balance moves of both markets overlap and every command is served
+    fn markets_should_process_concurrent_orders_in_parallel() {
+        let config = Config { markets: vec![new_market_config(), MarketConfig { pair_id: 2, ..new_market_config() }], fee_tiers: vec![], journal: None, snapshot: None, ledger: None, api_keys: vec![] };
+        let is_timed_out = Arc::new(AtomicBool::new(false));
+        let balance_source: Arc<Box<dyn BalanceSourceExector>> = Arc::new(Box::new(RendezvousBalanceSource { inner: MemoryBalanceManager::new(), arrivals: Mutex::new(0), arrived: Condvar::new(), is_timed_out: is_timed_out.clone() }));
+        let balance_service = Arc::new(BalanceService::new(balance_source));
+        let journal_source: Arc<Box<dyn JournalSourceExector>> = Arc::new(Box::new(MemoryJournalManager::new()));
+        let mut engine_service = EngineService::new(balance_service.clone(), Arc::new(FeeService::new(vec![])), Arc::new(JournalService::new(journal_source)), Arc::new(NotificationService::new()), Arc::new(CandleService::new()));
+        engine_service.insert_markets_from_config(&config);
+        let engine_service = Arc::new(engine_service);
+
+        for user_id in 1..=50 {
+            engine_service.deposit(user_id, QUOTE_ASSET_ID, user_id as u64, Decimal::from(10_000)).unwrap();
+        }
+
+        let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(4).build().unwrap();
+
+        let tasks_engine_service = engine_service.clone();
+
+        let results = runtime.block_on(async move {
+            let handles: Vec<_> = (1..=50).flat_map(|user_id| [1, 2].map(|pair_id| {
+                let engine_service = tasks_engine_service.clone();
+                tokio::spawn(async move { engine_service.place_order(pair_id, new_limit_order_input(user_id, OrderSide::Bid, Decimal::from(10), Decimal::from(1))).await })
+            })).collect();
+
+            let mut results = vec![];
+            for handle in handles {
+                results.push(handle.await.unwrap());
+            }
+            results
+        });
+
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert!(!is_timed_out.load(Ordering::Relaxed));
+        assert_eq!(to_depth(block_on(engine_service.get_market_orderbook_state(1, None, None)).unwrap().bids), vec![[Decimal::from(10), Decimal::from(50)]]);
+        assert_eq!(to_depth(block_on(engine_service.get_market_orderbook_state(2, None, None)).unwrap().bids), vec![[Decimal::from(10), Decimal::from(50)]]);
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).frozen, Decimal::from(20));
+    }
+
+    #[test]
+    // Hammer one balance with concurrent credits and debits. No update is lost and it never goes negative
+    fn balance_changes_should_be_atomic_under_concurrent_load() {
+        let balance_service = new_balance_service();
+
+        deposit(&balance_service, 1, QUOTE_ASSET_ID, Decimal::from(1000));
+
+        let threads: Vec<_> = (0..8).map(|thread_id| {
+            let balance_service = balance_service.clone();
+            std::thread::spawn(move || {
+                let mut debited = 0;
+                for index in 0..500 {
+                    let business_id = thread_id * 1000 + index;
+                    balance_service.change_balance(new_context(), 2, QUOTE_ASSET_ID, BusinessType::Deposit, business_id, BalanceType::Available, Decimal::ONE).unwrap();
+                    if balance_service.change_balance(new_context(), 1, QUOTE_ASSET_ID, BusinessType::Withdraw, business_id, BalanceType::Available, -Decimal::ONE).is_ok() {
+                        debited += 1;
+                    }
+                }
+                debited
+            })
+        }).collect();
+
+        let debited: u32 = threads.into_iter().map(|thread| thread.join().unwrap()).sum();
+
+        assert_eq!(debited, 1000);
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::ZERO);
+        assert_eq!(balance_service.get_balance_status(2, QUOTE_ASSET_ID).available, Decimal::from(4000));
+    }
+
+    #[test]
+    // Apply a batch whose third leg overdraws. No leg of the batch is applied
+    fn balance_transaction_should_apply_all_or_none_of_its_legs() {
+        let balance_service = new_balance_service();
+
+        deposit(&balance_service, 1, QUOTE_ASSET_ID, Decimal::from(100));
+
+        let new_delta = |user_id: UserId, type_: BalanceType, amount: i64| BalanceDelta { user_id, asset_id: QUOTE_ASSET_ID, type_, amount: Decimal::from(amount) };
+
+        let result = balance_service.change_balances(new_context(), BusinessType::Trade, 1, &[new_delta(1, BalanceType::Available, -60), new_delta(2, BalanceType::Available, 60), new_delta(1, BalanceType::Available, -60), new_delta(2, BalanceType::Available, 60)]);
+
+        assert!(matches!(result, Err(AppError::UserBalanceExceeds)));
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(100));
+        assert_eq!(balance_service.get_balance_status(2, QUOTE_ASSET_ID).available, Decimal::ZERO);
+
+        let outputs = balance_service.change_balances(new_context(), BusinessType::Trade, 2, &[new_delta(1, BalanceType::Available, -60), new_delta(1, BalanceType::Frozen, 60), new_delta(1, BalanceType::Frozen, -60), new_delta(2, BalanceType::Available, 60)]).unwrap();
+
+        assert_eq!(outputs.len(), 4);
+        assert_eq!((outputs[1].available_balance, outputs[1].frozen_balance), (Decimal::from(40), Decimal::from(60)));
+        assert_eq!((outputs[2].available_balance, outputs[2].frozen_balance), (Decimal::from(40), Decimal::ZERO));
+        assert_eq!(outputs[3].total_balance, Decimal::from(60));
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).total, Decimal::from(40));
+    }
+
+    #[test]
+    // Settle a trade whose ask side has no frozen base left. Neither side nor the fee collector is credited
+    fn trade_settlement_should_not_create_money_when_a_leg_fails() {
+        let balance_service = new_balance_service();
+        let market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 2, QUOTE_ASSET_ID, Decimal::from(1000));
+
+        let maker_order = Order::new_limit(1, 1, BASE_ASSET_ID, QUOTE_ASSET_ID, OrderSide::Ask, Decimal::from(100), Decimal::from(2));
+        let taker_order = Order::new_limit(2, 2, BASE_ASSET_ID, QUOTE_ASSET_ID, OrderSide::Bid, Decimal::from(100), Decimal::from(2));
+        let trade = Trade::new(&taker_order, &maker_order, Decimal::from(2)).unwrap();
+
+        assert!(matches!(market.transfer_trade_balance(&trade), Err(AppError::UserBalanceExceeds)));
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).total, Decimal::ZERO);
+        assert_eq!(balance_service.get_balance_status(2, QUOTE_ASSET_ID).total, Decimal::from(1000));
+        assert_eq!(balance_service.get_balance_status(2, BASE_ASSET_ID).total, Decimal::ZERO);
+    }
+
+    #[test]
+    // Market bid with a quote budget walks the asks until the budget runs out. Market bid by quantity is funded from the walked cost and leftover is released
+    fn market_bid_should_be_funded_by_reservation() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(10));
+        deposit(&balance_service, 2, QUOTE_ASSET_ID, Decimal::from(1000));
+        deposit(&balance_service, 3, QUOTE_ASSET_ID, Decimal::from(1000));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(5))).unwrap();
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(102), Decimal::from(5))).unwrap();
+
+        let match_result = market.process_new_order(new_context(), NewOrder { limit_price: None, quantity: Decimal::ZERO, quote_amount: Some(Decimal::from(806)), ..new_limit_order_input(2, OrderSide::Bid, Decimal::ZERO, Decimal::ZERO) }).unwrap();
+
+        assert_eq!(match_result.taker_order.get_filled_quantity(), Decimal::from(8));
+        assert_eq!(balance_service.get_balance_status(2, QUOTE_ASSET_ID).available, Decimal::from(194));
+        assert_eq!(balance_service.get_balance_status(2, QUOTE_ASSET_ID).frozen, Decimal::ZERO);
+        assert_eq!(balance_service.get_balance_status(2, BASE_ASSET_ID).available, Decimal::from(8));
+
+        let match_result = market.process_new_order(new_context(), NewOrder { limit_price: None, ..new_limit_order_input(3, OrderSide::Bid, Decimal::ZERO, Decimal::from(5)) }).unwrap();
+
+        assert_eq!(match_result.taker_order.get_filled_quantity(), Decimal::from(2));
+        assert_eq!(balance_service.get_balance_status(3, QUOTE_ASSET_ID).available, Decimal::from(796));
+        assert_eq!(balance_service.get_balance_status(3, QUOTE_ASSET_ID).frozen, Decimal::ZERO);
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(1010));
+
+        let result = market.process_new_order(new_context(), NewOrder { limit_price: None, quantity: Decimal::from(1), quote_amount: Some(Decimal::from(100)), ..new_limit_order_input(2, OrderSide::Bid, Decimal::ZERO, Decimal::ZERO) });
+
+        assert!(matches!(result, Err(AppError::InvalidMarketOrderAmount)));
+    }
+
+    #[test]
+    // Market ask reserves its base quantity and settles from frozen balance
+    fn market_ask_should_settle_from_reserved_balance() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 1, QUOTE_ASSET_ID, Decimal::from(1000));
+        deposit(&balance_service, 2, BASE_ASSET_ID, Decimal::from(5));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Bid, Decimal::from(100), Decimal::from(3))).unwrap();
+
+        let match_result = market.process_new_order(new_context(), NewOrder { limit_price: None, ..new_limit_order_input(2, OrderSide::Ask, Decimal::ZERO, Decimal::from(5)) }).unwrap();
+
+        assert_eq!(match_result.taker_order.get_filled_quantity(), Decimal::from(3));
+        assert_eq!(balance_service.get_balance_status(2, BASE_ASSET_ID).available, Decimal::from(2));
+        assert_eq!(balance_service.get_balance_status(2, BASE_ASSET_ID).frozen, Decimal::ZERO);
+        assert_eq!(balance_service.get_balance_status(2, QUOTE_ASSET_ID).available, Decimal::from(300));
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).frozen, Decimal::ZERO);
+    }
+
+    #[test]
+    // Own resting bid meets own ask. Cancel newest drops the taker, cancel oldest drops the resting bid and releases its frozen quote then trades with the next bid
+    fn self_trade_should_cancel_newest_or_oldest_order() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 1, QUOTE_ASSET_ID, Decimal::from(1000));
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(10));
+        deposit(&balance_service, 2, QUOTE_ASSET_ID, Decimal::from(1000));
+
+        let maker_order = market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Bid, Decimal::from(100), Decimal::from(5))).unwrap().taker_order;
+        market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Bid, Decimal::from(100), Decimal::from(4))).unwrap();
+
+        let match_result = market.process_new_order(new_context(), NewOrder { self_trade_prevention: Some(SelfTradePreventionMode::CancelNewest), ..new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(3)) }).unwrap();
+
+        assert_eq!(match_result.taker_order.get_status(), OrderStatus::Cancelled);
+        assert!(match_result.trades.is_empty());
+        assert_eq!(match_result.self_trade_cancels.iter().map(|cancel| (cancel.order.get_id(), cancel.quantity)).collect::<Vec<(OrderId, OrderQuantity)>>(), vec![(match_result.taker_order.get_id(), Decimal::from(3))]);
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).frozen, Decimal::from(500));
+        assert_eq!(balance_service.get_balance_status(1, BASE_ASSET_ID).available, Decimal::from(10));
+
+        let match_result = market.process_new_order(new_context(), NewOrder { self_trade_prevention: Some(SelfTradePreventionMode::CancelOldest), ..new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(3)) }).unwrap();
+
+        assert_eq!(match_result.taker_order.get_status(), OrderStatus::Filled);
+        assert_eq!(match_result.trades.len(), 1);
+        assert_eq!(match_result.self_trade_cancels.len(), 1);
+        assert_eq!(match_result.self_trade_cancels[0].order.get_id(), maker_order.get_id());
+        assert_eq!(match_result.self_trade_cancels[0].order.get_status(), OrderStatus::Cancelled);
+        assert_eq!(match_result.self_trade_cancels[0].released_amount, Decimal::from(500));
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).frozen, Decimal::ZERO);
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(1300));
+        assert_eq!(balance_service.get_balance_status(1, BASE_ASSET_ID).available, Decimal::from(7));
+        assert_eq!(to_depth(market.get_orderbook_state(None, None).bids), vec![[Decimal::from(100), Decimal::from(1)]]);
+    }
+
+    #[test]
+    // Fill or kill bid with self-trade prevention against a book holding own asks. Own quantity does not count as liquidity so the order is rejected untouched
+    fn fill_or_kill_should_not_count_own_orders_with_self_trade_prevention() {
+        let balance_service = new_balance_service();
+        let mut market = new_market(balance_service.clone());
+
+        deposit(&balance_service, 1, QUOTE_ASSET_ID, Decimal::from(2000));
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(5));
+        deposit(&balance_service, 2, BASE_ASSET_ID, Decimal::from(10));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(5))).unwrap();
+        market.process_new_order(new_context(), new_limit_order_input(2, OrderSide::Ask, Decimal::from(100), Decimal::from(5))).unwrap();
+
+        for mode in [SelfTradePreventionMode::CancelOldest, SelfTradePreventionMode::CancelNewest] {
+            let result = market.process_new_order(new_context(), NewOrder { time_in_force: TimeInForce::FillOrKill, self_trade_prevention: Some(mode), ..new_limit_order_input(1, OrderSide::Bid, Decimal::from(100), Decimal::from(10)) });
+
+            assert!(matches!(result, Err(AppError::FillOrKillOrderNotFilled)));
+        }
+
+        assert_eq!(to_depth(market.get_orderbook_state(None, None).asks), vec![[Decimal::from(100), Decimal::from(10)]]);
+
+        let match_result = market.process_new_order(new_context(), NewOrder { time_in_force: TimeInForce::FillOrKill, self_trade_prevention: Some(SelfTradePreventionMode::CancelOldest), ..new_limit_order_input(1, OrderSide::Bid, Decimal::from(100), Decimal::from(5)) }).unwrap();
+
+        assert_eq!(match_result.taker_order.get_status(), OrderStatus::Filled);
+        assert_eq!(balance_service.get_balance_status(1, BASE_ASSET_ID).available, Decimal::from(10));
+        assert!(market.get_orderbook_state(None, None).asks.is_empty());
+    }
+
+    #[test]
+    // Decrement and cancel shrinks the larger resting ask by the smaller taker. Market wide cancel both drops the taker and the rest of the ask
+    fn self_trade_should_decrement_or_cancel_both_orders() {
+        let balance_service = new_balance_service();
+        let market_config = MarketConfig { self_trade_prevention: Some(SelfTradePreventionMode::CancelBoth), ..new_market_config() };
+        let mut market = Market::new(&market_config, balance_service.clone(), Arc::new(FeeService::new(vec![])), Arc::new(Sequencer::new()), Arc::new(Sequencer::new()));
+
+        deposit(&balance_service, 1, QUOTE_ASSET_ID, Decimal::from(1000));
+        deposit(&balance_service, 1, BASE_ASSET_ID, Decimal::from(10));
+
+        market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Ask, Decimal::from(100), Decimal::from(5))).unwrap();
+
+        let match_result = market.process_new_order(new_context(), NewOrder { self_trade_prevention: Some(SelfTradePreventionMode::DecrementAndCancel), ..new_limit_order_input(1, OrderSide::Bid, Decimal::from(100), Decimal::from(2)) }).unwrap();
+
+        assert_eq!(match_result.taker_order.get_status(), OrderStatus::Cancelled);
+        assert_eq!(match_result.self_trade_cancels.iter().map(|cancel| (cancel.order.get_status(), cancel.quantity)).collect::<Vec<(OrderStatus, OrderQuantity)>>(), vec![(OrderStatus::Cancelled, Decimal::from(2)), (OrderStatus::Open, Decimal::from(2))]);
+        assert_eq!(to_depth(market.get_orderbook_state(None, None).asks), vec![[Decimal::from(100), Decimal::from(3)]]);
+        assert_eq!(balance_service.get_balance_status(1, BASE_ASSET_ID).frozen, Decimal::from(3));
+        assert_eq!(balance_service.get_balance_status(1, BASE_ASSET_ID).available, Decimal::from(7));
+
+        let match_result = market.process_new_order(new_context(), new_limit_order_input(1, OrderSide::Bid, Decimal::from(100), Decimal::from(4))).unwrap();
+
+        assert_eq!(match_result.taker_order.get_status(), OrderStatus::Cancelled);
+        assert_eq!(match_result.self_trade_cancels.len(), 2);
+        assert!(market.get_orderbook_state(None, None).asks.is_empty());
+        assert!(market.get_orderbook_state(None, None).bids.is_empty());
+        assert_eq!(balance_service.get_balance_status(1, BASE_ASSET_ID).available, Decimal::from(10));
+        assert_eq!(balance_service.get_balance_status(1, QUOTE_ASSET_ID).available, Decimal::from(1000));
+        assert_eq!(balance_service.get_balance_status(1, BASE_ASSET_ID).frozen + balance_service.get_balance_status(1, QUOTE_ASSET_ID).frozen, Decimal::ZERO);
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,7 @@
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::common::errors::AppResult;
 
 pub mod repositories;
 pub mod service;
@@ -6,7 +9,7 @@ pub mod service;
 pub type UserId = u32;
 pub type AssetId = u32;
 
-#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BalanceType {
     Available,
     Frozen,
@@ -19,10 +22,41 @@ pub struct BalanceStatus {
     pub frozen: Decimal,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceEntry {
+    pub user_id: UserId,
+    pub asset_id: AssetId,
+    pub type_: BalanceType,
+    pub amount: Decimal,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BalanceDelta {
+    pub user_id: UserId,
+    pub asset_id: AssetId,
+    pub type_: BalanceType,
+    pub amount: Decimal,
+}
+
 pub trait BalanceSourceExector: Send + Sync {
     fn get(&self, user_id: UserId, type_: BalanceType, asset_id: AssetId) -> Decimal;
-    fn increase(&self, user_id: UserId, type_: BalanceType, asset_id: AssetId, amount: Decimal);
-    fn decrease(&self, user_id: UserId, type_: BalanceType, asset_id: AssetId, amount: Decimal);
+    fn increase(
+        &self,
+        user_id: UserId,
+        type_: BalanceType,
+        asset_id: AssetId,
+        amount: Decimal,
+    ) -> AppResult<BalanceStatus>;
+    fn decrease(
+        &self,
+        user_id: UserId,
+        type_: BalanceType,
+        asset_id: AssetId,
+        amount: Decimal,
+    ) -> AppResult<BalanceStatus>;
+    fn apply_deltas(&self, deltas: &[BalanceDelta]) -> AppResult<Vec<BalanceStatus>>;
     fn get_total(&self, user_id: UserId, asset_id: AssetId) -> Decimal;
     fn get_status(&self, user_id: UserId, asset_id: AssetId) -> BalanceStatus;
+    fn get_all(&self) -> Vec<BalanceEntry>;
+    fn restore(&self, entries: Vec<BalanceEntry>);
 }
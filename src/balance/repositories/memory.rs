@@ -1,10 +1,22 @@
-use std::{collections::HashMap, sync::RwLock};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeSet, HashMap},
+    hash::{Hash, Hasher},
+    sync::{RwLock, RwLockWriteGuard},
+};
 
 use rust_decimal::{prelude::Zero, Decimal};
 
-use crate::balance::{AssetId, BalanceSourceExector, BalanceStatus, BalanceType, UserId};
+use crate::{
+    balance::{
+        AssetId, BalanceDelta, BalanceEntry, BalanceSourceExector, BalanceStatus, BalanceType,
+        UserId,
+    },
+    common::errors::{AppError, AppResult},
+};
 
-#[derive(Hash, PartialEq, Eq)]
+const SHARD_COUNT: usize = 64;
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq)]
 pub struct BalancesKey {
     user_id: UserId,
     asset_id: AssetId,
@@ -14,78 +26,252 @@ pub struct BalancesKey {
 pub type Balances = HashMap<BalancesKey, Decimal>;
 
 pub struct MemoryBalanceManager {
-    balances: RwLock<Balances>,
+    shards: Vec<RwLock<Balances>>,
+}
+
+impl Default for MemoryBalanceManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MemoryBalanceManager {
     pub fn new() -> Self {
         Self {
-            balances: RwLock::new(HashMap::new()),
+            shards: (0..SHARD_COUNT)
+                .map(|_| RwLock::new(HashMap::new()))
+                .collect(),
         }
     }
 
-    pub fn get_by_key(&self, key: &BalancesKey) -> Decimal {
-        *self
-            .balances
-            .try_read()
-            .unwrap()
-            .get(key)
-            .unwrap_or(&Decimal::zero())
+    fn get_shard_index(user_id: UserId, asset_id: AssetId) -> usize {
+        let mut hasher = DefaultHasher::new();
+        (user_id, asset_id).hash(&mut hasher);
+
+        hasher.finish() as usize % SHARD_COUNT
     }
 
-    pub fn set_by_key(&self, key: BalancesKey, amount: Decimal) {
-        self.balances.try_write().unwrap().insert(key, amount);
+    fn get_shard(&self, user_id: UserId, asset_id: AssetId) -> &RwLock<Balances> {
+        &self.shards[Self::get_shard_index(user_id, asset_id)]
     }
 
-    pub fn set(&self, user_id: UserId, type_: BalanceType, asset_id: AssetId, amount: Decimal) {
-        self.set_by_key(
-            BalancesKey {
+    fn get_from_shard(
+        balances: &Balances,
+        user_id: UserId,
+        type_: BalanceType,
+        asset_id: AssetId,
+    ) -> Decimal {
+        *balances
+            .get(&BalancesKey {
                 user_id,
                 asset_id,
                 type_,
-            },
-            amount,
-        );
+            })
+            .unwrap_or(&Decimal::zero())
+    }
+
+    fn get_status_from_shard(
+        balances: &Balances,
+        user_id: UserId,
+        asset_id: AssetId,
+    ) -> BalanceStatus {
+        let available = Self::get_from_shard(balances, user_id, BalanceType::Available, asset_id);
+        let frozen = Self::get_from_shard(balances, user_id, BalanceType::Frozen, asset_id);
+
+        BalanceStatus {
+            available,
+            frozen,
+            total: available + frozen,
+        }
+    }
+
+    fn check_total(available: Decimal, frozen: Decimal) -> AppResult<()> {
+        available
+            .checked_add(frozen)
+            .map(|_| ())
+            .ok_or(AppError::BalanceOverflow)
     }
 }
 
 impl BalanceSourceExector for MemoryBalanceManager {
     fn get(&self, user_id: UserId, type_: BalanceType, asset_id: AssetId) -> Decimal {
-        self.get_by_key(&BalancesKey {
-            user_id,
-            asset_id,
-            type_,
-        })
+        let balances = self.get_shard(user_id, asset_id).read().unwrap();
+
+        Self::get_from_shard(&balances, user_id, type_, asset_id)
+    }
+
+    fn increase(
+        &self,
+        user_id: UserId,
+        type_: BalanceType,
+        asset_id: AssetId,
+        amount: Decimal,
+    ) -> AppResult<BalanceStatus> {
+        let mut balances = self.get_shard(user_id, asset_id).write().unwrap();
+
+        if Self::get_status_from_shard(&balances, user_id, asset_id)
+            .total
+            .checked_add(amount)
+            .is_none()
+        {
+            return Err(AppError::BalanceOverflow);
+        }
+
+        *balances
+            .entry(BalancesKey {
+                user_id,
+                asset_id,
+                type_,
+            })
+            .or_default() += amount;
+
+        Ok(Self::get_status_from_shard(&balances, user_id, asset_id))
     }
 
-    fn increase(&self, user_id: UserId, type_: BalanceType, asset_id: AssetId, amount: Decimal) {
-        let old_amount = self.get(user_id, type_, asset_id);
-        let new_amount = old_amount + amount;
+    fn decrease(
+        &self,
+        user_id: UserId,
+        type_: BalanceType,
+        asset_id: AssetId,
+        amount: Decimal,
+    ) -> AppResult<BalanceStatus> {
+        let mut balances = self.get_shard(user_id, asset_id).write().unwrap();
+
+        let balance = Self::get_from_shard(&balances, user_id, type_, asset_id);
+
+        if balance.lt(&amount) {
+            return Err(AppError::UserBalanceExceeds);
+        }
+
+        balances.insert(
+            BalancesKey {
+                user_id,
+                asset_id,
+                type_,
+            },
+            balance - amount,
+        );
 
-        self.set(user_id, type_, asset_id, new_amount)
+        Ok(Self::get_status_from_shard(&balances, user_id, asset_id))
     }
 
-    fn decrease(&self, user_id: UserId, type_: BalanceType, asset_id: AssetId, amount: Decimal) {
-        let old_amount = self.get(user_id, type_, asset_id);
-        let new_amount = old_amount - amount;
+    // Shards are locked in index order so concurrent transactions can't deadlock.
+    fn apply_deltas(&self, deltas: &[BalanceDelta]) -> AppResult<Vec<BalanceStatus>> {
+        let shard_indexes: BTreeSet<usize> = deltas
+            .iter()
+            .map(|delta| Self::get_shard_index(delta.user_id, delta.asset_id))
+            .collect();
+
+        let mut shards: HashMap<usize, RwLockWriteGuard<Balances>> = shard_indexes
+            .into_iter()
+            .map(|index| (index, self.shards[index].write().unwrap()))
+            .collect();
+
+        let get_key = |delta: &BalanceDelta, type_: BalanceType| BalancesKey {
+            user_id: delta.user_id,
+            asset_id: delta.asset_id,
+            type_,
+        };
+
+        let mut working_balances = Balances::new();
+
+        for delta in deltas {
+            let shard = &shards[&Self::get_shard_index(delta.user_id, delta.asset_id)];
+
+            for type_ in [BalanceType::Available, BalanceType::Frozen] {
+                working_balances
+                    .entry(get_key(delta, type_))
+                    .or_insert_with(|| {
+                        Self::get_from_shard(shard, delta.user_id, type_, delta.asset_id)
+                    });
+            }
+        }
+
+        let mut statuses = Vec::with_capacity(deltas.len());
+
+        for delta in deltas {
+            let balance = working_balances
+                .get_mut(&get_key(delta, delta.type_))
+                .unwrap();
+
+            *balance = balance
+                .checked_add(delta.amount)
+                .ok_or(AppError::BalanceOverflow)?;
 
-        self.set(user_id, type_, asset_id, new_amount)
+            if balance.is_sign_negative() && !balance.is_zero() {
+                return Err(AppError::UserBalanceExceeds);
+            }
+
+            Self::check_total(
+                working_balances[&get_key(delta, BalanceType::Available)],
+                working_balances[&get_key(delta, BalanceType::Frozen)],
+            )?;
+
+            statuses.push(Self::get_status_from_shard(
+                &working_balances,
+                delta.user_id,
+                delta.asset_id,
+            ));
+        }
+
+        for delta in deltas {
+            let key = get_key(delta, delta.type_);
+
+            shards
+                .get_mut(&Self::get_shard_index(delta.user_id, delta.asset_id))
+                .unwrap()
+                .insert(key, working_balances[&key]);
+        }
+
+        Ok(statuses)
     }
 
     fn get_total(&self, user_id: UserId, asset_id: AssetId) -> Decimal {
-        self.get(user_id, BalanceType::Available, asset_id)
-            + self.get(user_id, BalanceType::Frozen, asset_id)
+        self.get_status(user_id, asset_id).total
     }
 
     fn get_status(&self, user_id: UserId, asset_id: AssetId) -> BalanceStatus {
-        let available = self.get(user_id, BalanceType::Available, asset_id);
-        let frozen = self.get(user_id, BalanceType::Frozen, asset_id);
-        let total = self.get_total(user_id, asset_id);
+        let balances = self.get_shard(user_id, asset_id).read().unwrap();
 
-        BalanceStatus {
-            available,
-            frozen,
-            total,
+        Self::get_status_from_shard(&balances, user_id, asset_id)
+    }
+
+    fn get_all(&self) -> Vec<BalanceEntry> {
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                shard
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .map(|(key, amount)| BalanceEntry {
+                        user_id: key.user_id,
+                        asset_id: key.asset_id,
+                        type_: key.type_,
+                        amount: *amount,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn restore(&self, entries: Vec<BalanceEntry>) {
+        for shard in &self.shards {
+            shard.write().unwrap().clear();
+        }
+
+        for entry in entries {
+            self.get_shard(entry.user_id, entry.asset_id)
+                .write()
+                .unwrap()
+                .insert(
+                    BalancesKey {
+                        user_id: entry.user_id,
+                        asset_id: entry.asset_id,
+                        type_: entry.type_,
+                    },
+                    entry.amount,
+                );
         }
     }
 }
@@ -1,25 +1,38 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
 
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 
-use crate::common::{
-    errors::{AppError, AppResult},
-    time::{Time, Timestamp},
+use crate::{
+    common::{
+        errors::{AppError, AppResult},
+        time::Timestamp,
+    },
+    journal::CommandContext,
+    ledger::service::LedgerService,
+    notification::{service::NotificationService, UserEvent},
 };
 
-use super::{AssetId, BalanceSourceExector, BalanceStatus, BalanceType, UserId};
+use super::{
+    AssetId, BalanceDelta, BalanceEntry, BalanceSourceExector, BalanceStatus, BalanceType, UserId,
+};
 
 pub type BalanceSource = Box<dyn BalanceSourceExector>;
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BusinessType {
     Withdraw,
     Deposit,
     Trade,
+    Order,
 }
 
 pub type BusinessId = u64;
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChangeBalanceOutput {
     pub user_id: UserId,
     pub asset_id: AssetId,
@@ -33,13 +46,33 @@ pub struct ChangeBalanceOutput {
     pub created_at: Timestamp,
 }
 
+pub type BusinessKey = (BusinessType, BusinessId);
+
 pub struct BalanceService {
     source: Arc<BalanceSource>,
+    ledger_service: Option<Arc<LedgerService>>,
+    notification_service: Option<Arc<NotificationService>>,
+    business_outputs: RwLock<HashMap<BusinessKey, ChangeBalanceOutput>>,
 }
 
 impl BalanceService {
     pub fn new(source: Arc<BalanceSource>) -> Self {
-        Self { source }
+        Self {
+            source,
+            ledger_service: None,
+            notification_service: None,
+            business_outputs: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_ledger(mut self, ledger_service: Arc<LedgerService>) -> Self {
+        self.ledger_service = Some(ledger_service);
+        self
+    }
+
+    pub fn with_notifications(mut self, notification_service: Arc<NotificationService>) -> Self {
+        self.notification_service = Some(notification_service);
+        self
     }
 
     pub fn is_available_balance_enough(
@@ -61,8 +94,37 @@ impl BalanceService {
         self.source.get_status(user_id, asset_id)
     }
 
+    pub fn get_all_balances(&self) -> Vec<BalanceEntry> {
+        self.source.get_all()
+    }
+
+    pub fn restore_balances(&self, entries: Vec<BalanceEntry>) {
+        self.source.restore(entries)
+    }
+
+    pub fn get_business_outputs(&self) -> Vec<ChangeBalanceOutput> {
+        self.business_outputs
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub fn restore_business_outputs(&self, outputs: Vec<ChangeBalanceOutput>) {
+        let mut business_outputs = self.business_outputs.write().unwrap();
+
+        business_outputs.clear();
+
+        for output in outputs {
+            business_outputs.insert((output.business_type, output.business_id), output);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn change_balance(
         &self,
+        context: CommandContext,
         user_id: UserId,
         asset_id: AssetId,
         business_type: BusinessType,
@@ -72,33 +134,299 @@ impl BalanceService {
     ) -> AppResult<ChangeBalanceOutput> {
         let abs_amount = amount.abs();
 
-        if amount.is_sign_positive() {
+        let balance_status = if amount.is_sign_positive() {
             self.source
-                .increase(user_id, balance_type, asset_id, abs_amount);
+                .increase(user_id, balance_type, asset_id, abs_amount)?
         } else {
-            let total_balance = self.source.get(user_id, balance_type, asset_id);
+            self.source
+                .decrease(user_id, balance_type, asset_id, abs_amount)?
+        };
 
-            if total_balance.lt(&abs_amount) {
-                return Err(AppError::UserBalanceExceeds);
-            }
+        let delta = BalanceDelta {
+            user_id,
+            asset_id,
+            type_: balance_type,
+            amount,
+        };
 
-            self.source
-                .decrease(user_id, balance_type, asset_id, abs_amount);
-        }
+        self.record_change(context, business_type, business_id, &delta, balance_status)
+    }
+
+    pub fn change_balances(
+        &self,
+        context: CommandContext,
+        business_type: BusinessType,
+        business_id: BusinessId,
+        deltas: &[BalanceDelta],
+    ) -> AppResult<Vec<ChangeBalanceOutput>> {
+        let balance_statuses = self.source.apply_deltas(deltas)?;
 
-        let balance_status = self.source.get_status(user_id, asset_id);
+        deltas
+            .iter()
+            .zip(balance_statuses)
+            .map(|(delta, balance_status)| {
+                self.record_change(context, business_type, business_id, delta, balance_status)
+            })
+            .collect()
+    }
 
-        Ok(ChangeBalanceOutput {
-            user_id: user_id,
-            asset_id: asset_id,
-            business_type: business_type,
-            business_id: business_id,
-            balance_type: balance_type,
-            amount: amount,
+    fn record_change(
+        &self,
+        context: CommandContext,
+        business_type: BusinessType,
+        business_id: BusinessId,
+        delta: &BalanceDelta,
+        balance_status: BalanceStatus,
+    ) -> AppResult<ChangeBalanceOutput> {
+        let output = ChangeBalanceOutput {
+            user_id: delta.user_id,
+            asset_id: delta.asset_id,
+            business_type,
+            business_id,
+            balance_type: delta.type_,
+            amount: delta.amount,
             total_balance: balance_status.total,
             available_balance: balance_status.available,
             frozen_balance: balance_status.frozen,
-            created_at: Time::get_current_timestamp(),
-        })
+            created_at: context.timestamp,
+        };
+
+        if let Some(ledger_service) = &self.ledger_service {
+            ledger_service.record(context.journal_sequence, &output)?;
+        }
+
+        if let Some(notification_service) = &self.notification_service {
+            notification_service.publish(delta.user_id, UserEvent::Balance(output.clone()));
+        }
+
+        Ok(output)
+    }
+
+    pub fn deposit(
+        &self,
+        context: CommandContext,
+        user_id: UserId,
+        asset_id: AssetId,
+        business_id: BusinessId,
+        amount: Decimal,
+    ) -> AppResult<ChangeBalanceOutput> {
+        self.change_balance_once(
+            context,
+            user_id,
+            asset_id,
+            BusinessType::Deposit,
+            business_id,
+            amount,
+        )
+    }
+
+    pub fn withdraw(
+        &self,
+        context: CommandContext,
+        user_id: UserId,
+        asset_id: AssetId,
+        business_id: BusinessId,
+        amount: Decimal,
+    ) -> AppResult<ChangeBalanceOutput> {
+        self.change_balance_once(
+            context,
+            user_id,
+            asset_id,
+            BusinessType::Withdraw,
+            business_id,
+            -amount,
+        )
+    }
+
+    pub fn check_deposit(
+        &self,
+        user_id: UserId,
+        asset_id: AssetId,
+        business_id: BusinessId,
+        amount: Decimal,
+    ) -> AppResult<Option<ChangeBalanceOutput>> {
+        self.check_change_once(
+            user_id,
+            asset_id,
+            BusinessType::Deposit,
+            business_id,
+            amount,
+        )
+    }
+
+    pub fn check_withdraw(
+        &self,
+        user_id: UserId,
+        asset_id: AssetId,
+        business_id: BusinessId,
+        amount: Decimal,
+    ) -> AppResult<Option<ChangeBalanceOutput>> {
+        self.check_change_once(
+            user_id,
+            asset_id,
+            BusinessType::Withdraw,
+            business_id,
+            -amount,
+        )
+    }
+
+    fn check_change_once(
+        &self,
+        user_id: UserId,
+        asset_id: AssetId,
+        business_type: BusinessType,
+        business_id: BusinessId,
+        amount: Decimal,
+    ) -> AppResult<Option<ChangeBalanceOutput>> {
+        let business_key = (business_type, business_id);
+
+        if let Some(output) = Self::find_business_output(
+            &self.business_outputs.read().unwrap(),
+            business_key,
+            user_id,
+            asset_id,
+            amount,
+        )? {
+            return Ok(Some(output));
+        }
+
+        if amount.is_sign_negative()
+            && !self.is_available_balance_enough(user_id, asset_id, -amount)
+        {
+            return Err(AppError::UserBalanceExceeds);
+        }
+
+        if self
+            .get_balance_status(user_id, asset_id)
+            .total
+            .checked_add(amount)
+            .is_none()
+        {
+            return Err(AppError::BalanceOverflow);
+        }
+
+        Ok(None)
+    }
+
+    fn find_business_output(
+        business_outputs: &HashMap<BusinessKey, ChangeBalanceOutput>,
+        business_key: BusinessKey,
+        user_id: UserId,
+        asset_id: AssetId,
+        amount: Decimal,
+    ) -> AppResult<Option<ChangeBalanceOutput>> {
+        match business_outputs.get(&business_key) {
+            Some(output)
+                if output.user_id != user_id
+                    || output.asset_id != asset_id
+                    || output.amount != amount =>
+            {
+                Err(AppError::BusinessIdConflict)
+            }
+            output => Ok(output.cloned()),
+        }
+    }
+
+    fn change_balance_once(
+        &self,
+        context: CommandContext,
+        user_id: UserId,
+        asset_id: AssetId,
+        business_type: BusinessType,
+        business_id: BusinessId,
+        amount: Decimal,
+    ) -> AppResult<ChangeBalanceOutput> {
+        let business_key = (business_type, business_id);
+
+        let mut business_outputs = self.business_outputs.write().unwrap();
+
+        if let Some(output) =
+            Self::find_business_output(&business_outputs, business_key, user_id, asset_id, amount)?
+        {
+            return Ok(output);
+        }
+
+        let output = self.change_balance(
+            context,
+            user_id,
+            asset_id,
+            business_type,
+            business_id,
+            BalanceType::Available,
+            amount,
+        )?;
+
+        business_outputs.insert(business_key, output.clone());
+
+        Ok(output)
+    }
+
+    pub fn freeze_balance(
+        &self,
+        context: CommandContext,
+        user_id: UserId,
+        asset_id: AssetId,
+        business_type: BusinessType,
+        business_id: BusinessId,
+        amount: Decimal,
+    ) -> AppResult<Vec<ChangeBalanceOutput>> {
+        self.move_balance(
+            context,
+            user_id,
+            asset_id,
+            business_type,
+            business_id,
+            (BalanceType::Available, BalanceType::Frozen),
+            amount,
+        )
+    }
+
+    pub fn unfreeze_balance(
+        &self,
+        context: CommandContext,
+        user_id: UserId,
+        asset_id: AssetId,
+        business_type: BusinessType,
+        business_id: BusinessId,
+        amount: Decimal,
+    ) -> AppResult<Vec<ChangeBalanceOutput>> {
+        self.move_balance(
+            context,
+            user_id,
+            asset_id,
+            business_type,
+            business_id,
+            (BalanceType::Frozen, BalanceType::Available),
+            amount,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn move_balance(
+        &self,
+        context: CommandContext,
+        user_id: UserId,
+        asset_id: AssetId,
+        business_type: BusinessType,
+        business_id: BusinessId,
+        (from_type, to_type): (BalanceType, BalanceType),
+        amount: Decimal,
+    ) -> AppResult<Vec<ChangeBalanceOutput>> {
+        let deltas = [
+            BalanceDelta {
+                user_id,
+                asset_id,
+                type_: from_type,
+                amount: -amount,
+            },
+            BalanceDelta {
+                user_id,
+                asset_id,
+                type_: to_type,
+                amount,
+            },
+        ];
+
+        self.change_balances(context, business_type, business_id, &deltas)
     }
 }
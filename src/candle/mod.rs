@@ -0,0 +1,106 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::time::Timestamp,
+    engine::models::{
+        market::PairId,
+        order::{OrderPrice, OrderQuantity},
+    },
+};
+
+pub mod service;
+
+const MINUTE: Timestamp = 60 * 1000;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CandleInterval {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+    OneHour,
+    FourHours,
+    OneDay,
+}
+
+impl CandleInterval {
+    pub const ALL: [CandleInterval; 6] = [
+        CandleInterval::OneMinute,
+        CandleInterval::FiveMinutes,
+        CandleInterval::FifteenMinutes,
+        CandleInterval::OneHour,
+        CandleInterval::FourHours,
+        CandleInterval::OneDay,
+    ];
+
+    pub fn get_duration(&self) -> Timestamp {
+        match self {
+            CandleInterval::OneMinute => MINUTE,
+            CandleInterval::FiveMinutes => 5 * MINUTE,
+            CandleInterval::FifteenMinutes => 15 * MINUTE,
+            CandleInterval::OneHour => 60 * MINUTE,
+            CandleInterval::FourHours => 4 * 60 * MINUTE,
+            CandleInterval::OneDay => 24 * 60 * MINUTE,
+        }
+    }
+
+    pub fn get_open_time(&self, timestamp: Timestamp) -> Timestamp {
+        timestamp - timestamp % self.get_duration()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Candle {
+    pub open_time: Timestamp,
+    pub open: OrderPrice,
+    pub high: OrderPrice,
+    pub low: OrderPrice,
+    pub close: OrderPrice,
+    pub volume: OrderQuantity,
+    pub quote_volume: Decimal,
+    pub trade_count: u64,
+}
+
+impl Candle {
+    pub fn new(open_time: Timestamp, price: OrderPrice, quantity: OrderQuantity) -> Self {
+        Self {
+            open_time,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: quantity,
+            quote_volume: price * quantity,
+            trade_count: 1,
+        }
+    }
+
+    pub fn add_trade(&mut self, price: OrderPrice, quantity: OrderQuantity) {
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+        self.volume += quantity;
+        self.quote_volume += price * quantity;
+        self.trade_count += 1;
+    }
+
+    pub fn merge(&mut self, other: &Candle) {
+        self.high = self.high.max(other.high);
+        self.low = self.low.min(other.low);
+        self.close = other.close;
+        self.volume += other.volume;
+        self.quote_volume += other.quote_volume;
+        self.trade_count += other.trade_count;
+    }
+
+    pub fn get_close_time(&self, interval: CandleInterval) -> Timestamp {
+        self.open_time + interval.get_duration() - 1
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CandleSeries {
+    pub pair_id: PairId,
+    pub interval: CandleInterval,
+    pub candles: Vec<Candle>,
+}
@@ -0,0 +1,136 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::RwLock,
+};
+
+use tokio::sync::broadcast;
+
+use crate::{
+    common::{channel::publish, time::Timestamp},
+    engine::models::{market::PairId, trade::TradeEvent},
+};
+
+use super::{Candle, CandleInterval, CandleSeries};
+
+const MAX_CANDLES_PER_SERIES: usize = 10_000;
+const CANDLE_CHANNEL_CAPACITY: usize = 1024;
+
+type CandleKey = (PairId, CandleInterval);
+
+#[derive(Default)]
+pub struct CandleService {
+    candles: RwLock<HashMap<CandleKey, VecDeque<Candle>>>,
+    senders: RwLock<HashMap<CandleKey, broadcast::Sender<Candle>>>,
+}
+
+impl CandleService {
+    pub fn new() -> Self {
+        Self {
+            candles: RwLock::new(HashMap::new()),
+            senders: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn record_trade(&self, trade: &TradeEvent) {
+        let mut candles = self.candles.write().unwrap();
+        let senders = self.senders.read().unwrap();
+
+        for interval in CandleInterval::ALL {
+            let series = candles.entry((trade.pair_id, interval)).or_default();
+            let open_time = interval.get_open_time(trade.created_at);
+
+            let position = series.partition_point(|candle| candle.open_time < open_time);
+
+            match series.get_mut(position) {
+                Some(candle) if candle.open_time == open_time => {
+                    candle.add_trade(trade.price, trade.quantity)
+                }
+                _ => series.insert(
+                    position,
+                    Candle::new(open_time, trade.price, trade.quantity),
+                ),
+            }
+
+            if let Some(sender) = senders.get(&(trade.pair_id, interval)) {
+                publish(sender, series[position].clone());
+            }
+
+            if series.len() > MAX_CANDLES_PER_SERIES {
+                series.pop_front();
+            }
+        }
+    }
+
+    pub fn get_candles(
+        &self,
+        pair_id: PairId,
+        interval: CandleInterval,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Vec<Candle> {
+        let candles = self.candles.read().unwrap();
+
+        let Some(series) = candles.get(&(pair_id, interval)) else {
+            return vec![];
+        };
+
+        let start = series.partition_point(|candle| candle.open_time < from);
+
+        series
+            .range(start..)
+            .take_while(|candle| candle.open_time <= to)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_merged_candle(
+        &self,
+        pair_id: PairId,
+        interval: CandleInterval,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Option<Candle> {
+        self.get_candles(pair_id, interval, from, to)
+            .into_iter()
+            .reduce(|mut merged, candle| {
+                merged.merge(&candle);
+                merged
+            })
+    }
+
+    pub fn subscribe(
+        &self,
+        pair_id: PairId,
+        interval: CandleInterval,
+    ) -> broadcast::Receiver<Candle> {
+        let mut senders = self.senders.write().unwrap();
+
+        senders
+            .entry((pair_id, interval))
+            .or_insert_with(|| broadcast::channel(CANDLE_CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    pub fn get_state(&self) -> Vec<CandleSeries> {
+        self.candles
+            .read()
+            .unwrap()
+            .iter()
+            .map(|((pair_id, interval), candles)| CandleSeries {
+                pair_id: *pair_id,
+                interval: *interval,
+                candles: candles.iter().cloned().collect(),
+            })
+            .collect()
+    }
+
+    pub fn restore_state(&self, state: Vec<CandleSeries>) {
+        let mut candles = self.candles.write().unwrap();
+
+        candles.clear();
+
+        for series in state {
+            candles.insert((series.pair_id, series.interval), series.candles.into());
+        }
+    }
+}
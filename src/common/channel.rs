@@ -0,0 +1,6 @@
+use tokio::sync::broadcast;
+
+// Sending only fails when nobody is subscribed, which is fine to ignore.
+pub fn publish<T>(sender: &broadcast::Sender<T>, value: T) {
+    let _ = sender.send(value);
+}
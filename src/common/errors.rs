@@ -7,6 +7,9 @@ pub enum AppError {
     #[error("User doesn't have enough balance.")]
     UserBalanceExceeds,
 
+    #[error("Balance would grow beyond the largest supported amount.")]
+    BalanceOverflow,
+
     #[error("Can't insert order with no limit price in orderbook.")]
     OrderbookInsertWithNoLimitPrice,
 
@@ -37,6 +40,24 @@ pub enum AppError {
     #[error("Order quantity must be greater than minimum market quantity amount.")]
     MarketMinimumAllowedQuantityExceeds,
 
+    #[error("Order quantity must be less than maximum market quantity amount.")]
+    MarketMaximumAllowedQuantityExceeds,
+
+    #[error("Order price must be a multiple of market price tick.")]
+    OrderPriceTickMismatch,
+
+    #[error("Order quantity must be a multiple of market quantity step.")]
+    OrderQuantityStepMismatch,
+
+    #[error("Order notional must be greater than minimum market notional.")]
+    MarketMinimumNotionalExceeds,
+
+    #[error("Order notional must be less than maximum market notional.")]
+    MarketMaximumNotionalExceeds,
+
+    #[error("Order amount is too large.")]
+    OrderAmountOverflow,
+
     #[error("Order limit price is invalid.")]
     LimitOrderInvalidPrice,
 
@@ -48,4 +69,52 @@ pub enum AppError {
 
     #[error("Market with this pair ID does'nt found.")]
     MarketNotFound,
+
+    #[error("Order doesn't belong to this user.")]
+    OrderUserMismatch,
+
+    #[error("Fill or kill order can't be filled completely.")]
+    FillOrKillOrderNotFilled,
+
+    #[error("Post only order must be a good till cancel limit order.")]
+    PostOnlyOrderInvalid,
+
+    #[error("Post only order would match with counter orderbook.")]
+    PostOnlyOrderWouldMatch,
+
+    #[error("Order stop price is invalid.")]
+    StopOrderInvalidPrice,
+
+    #[error("Business ID is already used by a different balance change.")]
+    BusinessIdConflict,
+
+    #[error("Market is busy with too many pending commands.")]
+    MarketOverloaded,
+
+    #[error("Market has stopped processing commands.")]
+    MarketStopped,
+
+    #[error("Journal operation failed: {0}")]
+    JournalFailed(String),
+
+    #[error("Snapshot operation failed: {0}")]
+    SnapshotFailed(String),
+
+    #[error("Command failed unexpectedly.")]
+    CommandPanicked,
+
+    #[error("Ledger operation failed: {0}")]
+    LedgerFailed(String),
+
+    #[error("Request is not authenticated.")]
+    Unauthenticated,
+
+    #[error("Request requires admin credentials.")]
+    AdminRequired,
+
+    #[error("Subscriber lagged behind and missed {0} messages.")]
+    SubscriberLagged(u64),
+
+    #[error("Request field {0} is invalid: {1}")]
+    InvalidRequestField(String, String),
 }
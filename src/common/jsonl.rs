@@ -0,0 +1,36 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+};
+
+use serde::de::DeserializeOwned;
+
+// A crash in the middle of an append leaves a torn last line behind. It is cut off, otherwise
+// new entries would land after it where reading never reaches them.
+pub fn read_and_truncate_torn_tail<T: DeserializeOwned>(file: &File) -> io::Result<Vec<T>> {
+    let mut reader = BufReader::new(file);
+    let mut line = vec![];
+    let mut valid_len = 0;
+    let mut entries = vec![];
+
+    loop {
+        line.clear();
+
+        let read = reader.read_until(b'\n', &mut line)?;
+
+        if read == 0 || line.last() != Some(&b'\n') {
+            break;
+        }
+
+        let Ok(entry) = serde_json::from_slice::<T>(&line) else {
+            break;
+        };
+
+        valid_len += read as u64;
+        entries.push(entry);
+    }
+
+    file.set_len(valid_len)?;
+
+    Ok(entries)
+}
@@ -1,3 +1,5 @@
+pub mod channel;
 pub mod errors;
+pub mod jsonl;
 pub mod sequencer;
 pub mod time;
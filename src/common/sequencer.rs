@@ -1,5 +1,6 @@
 use std::sync::atomic::{AtomicU64, Ordering};
 
+#[derive(Default)]
 pub struct Sequencer {
     index: AtomicU64,
 }
@@ -19,4 +20,8 @@ impl Sequencer {
         self.index.fetch_add(1, Ordering::Relaxed);
         self.index.load(Ordering::Relaxed)
     }
+
+    pub fn set(&self, index: u64) {
+        self.index.store(index, Ordering::Relaxed);
+    }
 }
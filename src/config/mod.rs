@@ -1,20 +1,84 @@
 use rust_decimal::Decimal;
 use serde::Deserialize;
 
-use crate::{balance::AssetId, engine::models::market::PairId};
+use crate::{
+    balance::{AssetId, UserId},
+    engine::models::{market::PairId, order::SelfTradePreventionMode},
+    fee::FeeTierLevel,
+};
 
 pub mod repositories;
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub markets: Vec<MarketConfig>,
+    #[serde(default)]
+    pub fee_tiers: Vec<FeeTierConfig>,
+    pub journal: Option<JournalConfig>,
+    pub snapshot: Option<SnapshotConfig>,
+    pub ledger: Option<LedgerConfig>,
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct MarketConfig {
     pub pair_id: PairId,
     pub base_asset_id: AssetId,
     pub quote_asset_id: AssetId,
     pub is_market_trade_enabled: bool,
     pub min_allowed_quantity: Decimal,
+    #[serde(default)]
+    pub max_quantity: Option<Decimal>,
+    #[serde(default)]
+    pub price_tick: Option<Decimal>,
+    #[serde(default)]
+    pub quantity_step: Option<Decimal>,
+    #[serde(default)]
+    pub min_notional: Option<Decimal>,
+    #[serde(default)]
+    pub max_notional: Option<Decimal>,
+    #[serde(default)]
+    pub maker_fee_rate: Decimal,
+    #[serde(default)]
+    pub taker_fee_rate: Decimal,
+    #[serde(default)]
+    pub fee_collector_user_id: UserId,
+    #[serde(default)]
+    pub self_trade_prevention: Option<SelfTradePreventionMode>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeeTierConfig {
+    pub level: FeeTierLevel,
+    pub min_volume: Decimal,
+    pub maker_fee_rate: Decimal,
+    pub taker_fee_rate: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JournalConfig {
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshotConfig {
+    pub path: String,
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+    #[serde(default)]
+    pub retain_count: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LedgerConfig {
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKeyConfig {
+    pub user_id: UserId,
+    pub key: String,
+    #[serde(default)]
+    pub is_admin: bool,
 }
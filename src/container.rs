@@ -4,29 +4,99 @@ use crate::{
     balance::{
         repositories::memory::MemoryBalanceManager, service::BalanceService, BalanceSourceExector,
     },
-    common::sequencer::Sequencer,
+    candle::service::CandleService,
     config::Config,
     engine::service::EngineService,
+    fee::service::FeeService,
+    journal::{
+        repositories::{file::FileJournalManager, memory::MemoryJournalManager},
+        service::JournalService,
+        JournalSourceExector,
+    },
+    ledger::{
+        repositories::{file::FileLedgerManager, memory::MemoryLedgerManager},
+        service::LedgerService,
+        LedgerSourceExector,
+    },
+    notification::service::NotificationService,
+    snapshot::service::SnapshotService,
 };
 
 pub struct Container {
     pub balance_service: Arc<BalanceService>,
     pub engine_service: Arc<EngineService>,
+    pub fee_service: Arc<FeeService>,
+    pub snapshot_service: Option<Arc<SnapshotService>>,
+    pub ledger_service: Arc<LedgerService>,
+    pub notification_service: Arc<NotificationService>,
 }
 
 impl Container {
     pub fn new(config: &Config) -> Self {
+        let fee_service = Arc::new(FeeService::new(config.fee_tiers.clone()));
+
+        let journal_source: Arc<Box<dyn JournalSourceExector>> = match &config.journal {
+            Some(journal_config) => Arc::new(Box::new(
+                FileJournalManager::open(&journal_config.path).unwrap(),
+            )),
+            None => Arc::new(Box::new(MemoryJournalManager::new())),
+        };
+        let journal_service = Arc::new(JournalService::new(journal_source));
+
+        let ledger_source: Arc<Box<dyn LedgerSourceExector>> = match &config.ledger {
+            Some(ledger_config) => Arc::new(Box::new(
+                FileLedgerManager::open(&ledger_config.path).unwrap(),
+            )),
+            None => Arc::new(Box::new(MemoryLedgerManager::new())),
+        };
+        let ledger_service =
+            Arc::new(LedgerService::new(ledger_source, journal_service.clone()).unwrap());
+
         let balance_source: Arc<Box<dyn BalanceSourceExector>> =
             Arc::new(Box::new(MemoryBalanceManager::new()));
-        let balance_service = Arc::new(BalanceService::new(balance_source.clone()));
+        let notification_service = Arc::new(NotificationService::new());
+
+        let balance_service = Arc::new(
+            BalanceService::new(balance_source.clone())
+                .with_ledger(ledger_service.clone())
+                .with_notifications(notification_service.clone()),
+        );
 
-        let mut engine_service = EngineService::new(balance_service.clone());
+        let candle_service = Arc::new(CandleService::new());
+
+        let mut engine_service = EngineService::new(
+            balance_service.clone(),
+            fee_service.clone(),
+            journal_service,
+            notification_service.clone(),
+            candle_service,
+        );
 
         engine_service.insert_markets_from_config(config);
 
+        let engine_service = Arc::new(engine_service);
+
+        let snapshot_service = config.snapshot.as_ref().map(|snapshot_config| {
+            Arc::new(SnapshotService::new(
+                engine_service.clone(),
+                &snapshot_config.path,
+                snapshot_config.retain_count,
+            ))
+        });
+
+        if let Some(snapshot_service) = &snapshot_service {
+            snapshot_service.restore_latest_snapshot().unwrap();
+        }
+
+        engine_service.replay_journal().unwrap();
+
         Self {
             balance_service,
-            engine_service: Arc::new(engine_service),
+            engine_service,
+            fee_service,
+            snapshot_service,
+            ledger_service,
+            notification_service,
         }
     }
 }
@@ -0,0 +1,203 @@
+use std::{sync::Arc, thread};
+
+use tokio::sync::{broadcast, mpsc, oneshot};
+
+use crate::{
+    balance::UserId,
+    common::{
+        errors::{AppError, AppResult},
+        time::Timestamp,
+    },
+    fee::UserFeeStatus,
+    journal::{catch_command_panic, service::JournalService, Command, JournalEntry},
+    ticker::Ticker,
+};
+
+use super::models::{
+    market::{Market, MarketSnapshot, OrderbookState, PairId},
+    order::{NewOrder, Order, OrderId, OrderPrice},
+    orderbook::{MatchOrderOutput, OrderbookDelta},
+    trade::TradeEvent,
+};
+
+const MARKET_COMMAND_CHANNEL_CAPACITY: usize = 1024;
+
+pub type Reply<T> = oneshot::Sender<T>;
+
+pub enum MarketCommand {
+    PlaceOrder {
+        new_order: NewOrder,
+        reply: Reply<AppResult<MatchOrderOutput>>,
+    },
+    CancelOrder {
+        user_id: UserId,
+        order_id: OrderId,
+        reply: Reply<AppResult<Order>>,
+    },
+    Replay {
+        entry: JournalEntry,
+        reply: Reply<AppResult<()>>,
+    },
+    GetOrderbookState {
+        limit: Option<usize>,
+        price_group: Option<OrderPrice>,
+        reply: Reply<OrderbookState>,
+    },
+    GetUserFeeStatus {
+        user_id: UserId,
+        now: Timestamp,
+        reply: Reply<UserFeeStatus>,
+    },
+    GetTicker {
+        now: Timestamp,
+        reply: Reply<Ticker>,
+    },
+    SubscribeTrades {
+        reply: Reply<broadcast::Receiver<TradeEvent>>,
+    },
+    SubscribeOrderbook {
+        reply: Reply<(OrderbookState, broadcast::Receiver<OrderbookDelta>)>,
+    },
+    GetSnapshot {
+        reply: Reply<MarketSnapshot>,
+        resume: oneshot::Receiver<()>,
+    },
+    RestoreSnapshot {
+        snapshot: Box<MarketSnapshot>,
+        reply: Reply<()>,
+    },
+}
+
+#[derive(Clone)]
+pub struct MarketHandle {
+    sender: mpsc::Sender<MarketCommand>,
+}
+
+impl MarketHandle {
+    pub fn spawn(pair_id: PairId, market: Market, journal_service: Arc<JournalService>) -> Self {
+        let (sender, receiver) = mpsc::channel(MARKET_COMMAND_CHANNEL_CAPACITY);
+
+        thread::Builder::new()
+            .name(format!("market-{}", pair_id))
+            .spawn(move || run(pair_id, market, receiver, journal_service))
+            .unwrap();
+
+        Self { sender }
+    }
+
+    pub fn reserve(&self) -> AppResult<mpsc::Permit<'_, MarketCommand>> {
+        self.sender.try_reserve().map_err(|err| match err {
+            mpsc::error::TrySendError::Full(_) => AppError::MarketOverloaded,
+            mpsc::error::TrySendError::Closed(_) => AppError::MarketStopped,
+        })
+    }
+
+    pub async fn request<T>(
+        &self,
+        command: impl FnOnce(Reply<T>) -> MarketCommand,
+    ) -> AppResult<T> {
+        let (reply, receiver) = oneshot::channel();
+
+        self.reserve()?.send(command(reply));
+
+        receiver.await.map_err(|_| AppError::MarketStopped)
+    }
+
+    // Waits for the market on the current thread, so it must never be called from async code.
+    pub fn blocking_request<T>(
+        &self,
+        command: impl FnOnce(Reply<T>) -> MarketCommand,
+    ) -> AppResult<T> {
+        let (reply, receiver) = oneshot::channel();
+
+        self.sender
+            .blocking_send(command(reply))
+            .map_err(|_| AppError::MarketStopped)?;
+
+        receiver
+            .blocking_recv()
+            .map_err(|_| AppError::MarketStopped)
+    }
+}
+
+fn run(
+    pair_id: PairId,
+    mut market: Market,
+    mut receiver: mpsc::Receiver<MarketCommand>,
+    journal_service: Arc<JournalService>,
+) {
+    while let Some(command) = receiver.blocking_recv() {
+        match command {
+            MarketCommand::PlaceOrder { new_order, reply } => {
+                let command = Command::PlaceOrder { pair_id, new_order };
+
+                let _ = reply.send(market.check_new_order(new_order).and_then(|_| {
+                    journal_service.execute(command, |context| {
+                        market.process_new_order(context, new_order)
+                    })
+                }));
+            }
+            MarketCommand::CancelOrder {
+                user_id,
+                order_id,
+                reply,
+            } => {
+                let command = Command::CancelOrder {
+                    pair_id,
+                    user_id,
+                    order_id,
+                };
+
+                let _ = reply.send(market.check_cancel_order(user_id, order_id).and_then(|_| {
+                    journal_service.execute(command, |context| {
+                        market.cancel_order(context, user_id, order_id)
+                    })
+                }));
+            }
+            MarketCommand::Replay { entry, reply } => {
+                let context = entry.get_context();
+
+                let _ = reply.send(catch_command_panic(|| match entry.command {
+                    Command::PlaceOrder { new_order, .. } => {
+                        market.process_new_order(context, new_order).map(|_| ())
+                    }
+                    Command::CancelOrder {
+                        user_id, order_id, ..
+                    } => market.cancel_order(context, user_id, order_id).map(|_| ()),
+                    _ => Ok(()),
+                }));
+            }
+            MarketCommand::GetOrderbookState {
+                limit,
+                price_group,
+                reply,
+            } => {
+                let _ = reply.send(market.get_orderbook_state(limit, price_group));
+            }
+            MarketCommand::GetUserFeeStatus {
+                user_id,
+                now,
+                reply,
+            } => {
+                let _ = reply.send(market.get_user_fee_status(user_id, now));
+            }
+            MarketCommand::GetTicker { now, reply } => {
+                let _ = reply.send(market.get_ticker(now));
+            }
+            MarketCommand::SubscribeTrades { reply } => {
+                let _ = reply.send(market.subscribe_trades());
+            }
+            MarketCommand::SubscribeOrderbook { reply } => {
+                let _ = reply.send(market.subscribe_orderbook());
+            }
+            MarketCommand::GetSnapshot { reply, resume } => {
+                let _ = reply.send(market.get_snapshot());
+                let _ = resume.blocking_recv();
+            }
+            MarketCommand::RestoreSnapshot { snapshot, reply } => {
+                market.restore_snapshot(*snapshot);
+                let _ = reply.send(());
+            }
+        }
+    }
+}
@@ -1,2 +1,3 @@
+pub mod actor;
 pub mod models;
 pub mod service;
@@ -0,0 +1,128 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, VecDeque},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::errors::{AppError, AppResult};
+
+use super::{
+    order::{Order, OrderId, OrderPrice, OrderSide},
+    orderbook::OrdersIndex,
+};
+
+pub type BuyStopOrders = BTreeMap<OrderPrice, VecDeque<OrderId>>;
+pub type SellStopOrders = BTreeMap<Reverse<OrderPrice>, VecDeque<OrderId>>;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ConditionalOrderbook {
+    buy_stops: BuyStopOrders,
+    sell_stops: SellStopOrders,
+    orders: OrdersIndex,
+}
+
+impl Default for ConditionalOrderbook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConditionalOrderbook {
+    pub fn new() -> Self {
+        Self {
+            buy_stops: BTreeMap::new(),
+            sell_stops: BTreeMap::new(),
+            orders: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, order: Order) -> AppResult<()> {
+        let stop_price = order
+            .get_stop_price()
+            .ok_or(AppError::StopOrderInvalidPrice)?;
+
+        match order.get_side() {
+            OrderSide::Ask => self
+                .sell_stops
+                .entry(Reverse(stop_price))
+                .or_default()
+                .push_back(order.get_id()),
+            OrderSide::Bid => self
+                .buy_stops
+                .entry(stop_price)
+                .or_default()
+                .push_back(order.get_id()),
+        }
+
+        self.orders.insert(order.get_id(), order);
+
+        Ok(())
+    }
+
+    pub fn cancel_order(&mut self, order_id: OrderId) -> AppResult<Order> {
+        let mut order = self
+            .orders
+            .remove(&order_id)
+            .ok_or(AppError::OrderIdNotFound)?;
+
+        let stop_price = order
+            .get_stop_price()
+            .ok_or(AppError::StopOrderInvalidPrice)?;
+
+        let order_ids = match order.get_side() {
+            OrderSide::Ask => self.sell_stops.get_mut(&Reverse(stop_price)),
+            OrderSide::Bid => self.buy_stops.get_mut(&stop_price),
+        };
+
+        if let Some(order_ids) = order_ids {
+            order_ids.retain(|&id| id != order_id);
+
+            if order_ids.is_empty() {
+                match order.get_side() {
+                    OrderSide::Ask => self.sell_stops.remove(&Reverse(stop_price)),
+                    OrderSide::Bid => self.buy_stops.remove(&stop_price),
+                };
+            }
+        }
+
+        order.cancel();
+
+        Ok(order)
+    }
+
+    pub fn get_order(&self, order_id: OrderId) -> Option<&Order> {
+        self.orders.get(&order_id)
+    }
+
+    pub fn take_triggered_orders(&mut self, last_trade_price: OrderPrice) -> Vec<Order> {
+        let mut triggered_order_ids: Vec<OrderId> = vec![];
+
+        while let Some(entry) = self.buy_stops.first_entry() {
+            if *entry.key() > last_trade_price {
+                break;
+            }
+
+            triggered_order_ids.extend(entry.remove());
+        }
+
+        while let Some(entry) = self.sell_stops.first_entry() {
+            if entry.key().0 < last_trade_price {
+                break;
+            }
+
+            triggered_order_ids.extend(entry.remove());
+        }
+
+        triggered_order_ids.sort_unstable();
+
+        triggered_order_ids
+            .iter()
+            .filter_map(|order_id| self.orders.remove(order_id))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+}
@@ -1,105 +1,273 @@
 use std::sync::Arc;
 
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
 use crate::{
     balance::{
-        service::{BalanceService, BusinessType},
-        AssetId, BalanceType, UserId,
+        service::{BalanceService, BusinessType, ChangeBalanceOutput},
+        AssetId, BalanceDelta, BalanceType, UserId,
     },
+    candle::{service::CandleService, CandleInterval},
     common::{
+        channel::publish,
         errors::{AppError, AppResult},
         sequencer::Sequencer,
+        time::Timestamp,
     },
+    config::MarketConfig,
+    fee::{service::FeeService, FeeRates, UserFeeStatus},
+    journal::CommandContext,
+    notification::{service::NotificationService, OrderEvent, OrderEventType, UserEvent},
+    ticker::{Ticker, TICKER_WINDOW},
 };
 
 use super::{
-    order::{Order, OrderPrice, OrderQuantity, OrderSide},
-    orderbook::{Orderbook, OrderbookDepth},
-    trade::Trade,
+    conditional_orderbook::ConditionalOrderbook,
+    order::{
+        NewOrder, Order, OrderAmount, OrderId, OrderPrice, OrderQuantity, OrderSide, OrderStatus,
+        SelfTradePreventionMode, TimeInForce,
+    },
+    orderbook::{BookSequence, DepthLevel, MatchOrderOutput, Orderbook, OrderbookDelta},
+    trade::{Trade, TradeEvent},
 };
 
 pub type PairId = u32;
 
+const TRADE_CHANNEL_CAPACITY: usize = 1024;
+const ORDERBOOK_CHANNEL_CAPACITY: usize = 4096;
+const MARKET_ORDER_QUANTITY_SCALE: u32 = 8;
+
+#[derive(Debug, Clone)]
+pub struct OrderbookState {
+    pub sequence: BookSequence,
+    pub asks: Vec<DepthLevel>,
+    pub bids: Vec<DepthLevel>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MarketSnapshot {
+    pub orderbook: Orderbook,
+    pub conditional_orderbook: ConditionalOrderbook,
+    pub last_trade_price: Option<OrderPrice>,
+    pub order_id_sequence: OrderId,
+}
+
 pub struct Market {
+    pair_id: PairId,
     base_asset_id: AssetId,
     quote_asset_id: AssetId,
     is_market_trade_enabled: bool,
     min_allowed_quantity: OrderQuantity,
-
+    max_quantity: Option<OrderQuantity>,
+    price_tick: Option<OrderPrice>,
+    quantity_step: Option<OrderQuantity>,
+    min_notional: Option<OrderAmount>,
+    max_notional: Option<OrderAmount>,
+    fee_rates: FeeRates,
+    fee_collector_user_id: UserId,
+    self_trade_prevention: Option<SelfTradePreventionMode>,
+
+    context: CommandContext,
     orderbook: Orderbook,
+    conditional_orderbook: ConditionalOrderbook,
+    last_trade_price: Option<OrderPrice>,
     order_id_sequencer: Arc<Sequencer>,
+    trade_id_sequencer: Arc<Sequencer>,
     balance_service: Arc<BalanceService>,
+    fee_service: Arc<FeeService>,
+    notification_service: Option<Arc<NotificationService>>,
+    candle_service: Option<Arc<CandleService>>,
+    trade_sender: broadcast::Sender<TradeEvent>,
+    orderbook_sender: broadcast::Sender<OrderbookDelta>,
 }
 
 impl Market {
     pub fn new(
-        base_asset_id: AssetId,
-        quote_asset_id: AssetId,
-        is_market_trade_enabled: bool,
-        min_allowed_quantity: OrderQuantity,
+        config: &MarketConfig,
         balance_service: Arc<BalanceService>,
+        fee_service: Arc<FeeService>,
         order_id_sequencer: Arc<Sequencer>,
+        trade_id_sequencer: Arc<Sequencer>,
     ) -> Self {
+        let (trade_sender, _) = broadcast::channel(TRADE_CHANNEL_CAPACITY);
+        let (orderbook_sender, _) = broadcast::channel(ORDERBOOK_CHANNEL_CAPACITY);
+
         Self {
-            base_asset_id,
-            quote_asset_id,
-            orderbook: Orderbook::new(),
+            pair_id: config.pair_id,
+            context: CommandContext::default(),
+            base_asset_id: config.base_asset_id,
+            quote_asset_id: config.quote_asset_id,
+            orderbook: Orderbook::new().with_price_tick(config.price_tick),
+            conditional_orderbook: ConditionalOrderbook::new(),
+            last_trade_price: None,
             balance_service,
-
-            is_market_trade_enabled,
-            min_allowed_quantity,
+            fee_service,
+            notification_service: None,
+            candle_service: None,
+
+            is_market_trade_enabled: config.is_market_trade_enabled,
+            min_allowed_quantity: config.min_allowed_quantity,
+            max_quantity: config.max_quantity,
+            price_tick: config.price_tick,
+            quantity_step: config.quantity_step,
+            min_notional: config.min_notional,
+            max_notional: config.max_notional,
+            fee_rates: FeeRates {
+                maker_fee_rate: config.maker_fee_rate,
+                taker_fee_rate: config.taker_fee_rate,
+            },
+            fee_collector_user_id: config.fee_collector_user_id,
+            self_trade_prevention: config.self_trade_prevention,
             order_id_sequencer,
+            trade_id_sequencer,
+            trade_sender,
+            orderbook_sender,
         }
     }
 
-    pub fn freeze_user_balance(&self, order: &Order) -> AppResult<()> {
-        let remaining_amount = order.get_frozen_amount();
+    pub fn with_notifications(mut self, notification_service: Arc<NotificationService>) -> Self {
+        self.notification_service = Some(notification_service);
+        self
+    }
 
-        self.balance_service.change_balance(
-            order.get_user_id(),
-            order.get_asset_id(),
-            BusinessType::Trade,
-            1,
-            BalanceType::Available,
-            -remaining_amount,
-        )?;
+    pub fn with_candles(mut self, candle_service: Arc<CandleService>) -> Self {
+        self.candle_service = Some(candle_service);
+        self
+    }
 
-        self.balance_service.change_balance(
-            order.get_user_id(),
-            order.get_asset_id(),
-            BusinessType::Trade,
-            1,
-            BalanceType::Frozen,
-            remaining_amount,
-        )?;
+    fn publish_order_event(&self, order: &Order, type_: OrderEventType) {
+        let Some(notification_service) = &self.notification_service else {
+            return;
+        };
 
-        Ok(())
+        let event = OrderEvent {
+            pair_id: self.pair_id,
+            type_,
+            order: *order,
+            created_at: self.context.timestamp,
+        };
+
+        notification_service.publish(order.get_user_id(), UserEvent::Order(event));
+    }
+
+    fn publish_order_status_event(&self, order: &Order) {
+        let type_ = match order.get_status() {
+            OrderStatus::PartiallyFilled => OrderEventType::PartiallyFilled,
+            OrderStatus::Filled => OrderEventType::Filled,
+            OrderStatus::Cancelled => OrderEventType::Cancelled,
+            OrderStatus::Open | OrderStatus::Closed => return,
+        };
+
+        self.publish_order_event(order, type_);
+    }
+
+    pub fn subscribe_trades(&self) -> broadcast::Receiver<TradeEvent> {
+        self.trade_sender.subscribe()
     }
 
-    pub fn unfreeze_user_balance(&self, order: &Order) -> AppResult<()> {
-        let remaining_amount = order.get_frozen_amount();
+    pub fn subscribe_orderbook(&self) -> (OrderbookState, broadcast::Receiver<OrderbookDelta>) {
+        let receiver = self.orderbook_sender.subscribe();
 
-        self.balance_service.change_balance(
+        (self.get_orderbook_state(None, None), receiver)
+    }
+
+    fn publish_orderbook_deltas(&mut self) {
+        for delta in self.orderbook.take_deltas() {
+            publish(&self.orderbook_sender, delta);
+        }
+    }
+
+    pub fn freeze_user_balance(&self, order: &Order) -> AppResult<Vec<ChangeBalanceOutput>> {
+        self.balance_service.freeze_balance(
+            self.context,
             order.get_user_id(),
             order.get_asset_id(),
-            BusinessType::Trade,
-            1,
-            BalanceType::Available,
-            -remaining_amount,
-        )?;
+            BusinessType::Order,
+            order.get_id(),
+            order.get_frozen_amount(),
+        )
+    }
 
-        self.balance_service.change_balance(
+    pub fn unfreeze_user_balance(&self, order: &Order) -> AppResult<Vec<ChangeBalanceOutput>> {
+        self.balance_service.unfreeze_balance(
+            self.context,
             order.get_user_id(),
             order.get_asset_id(),
-            BusinessType::Trade,
-            1,
-            BalanceType::Frozen,
-            remaining_amount,
-        )?;
+            BusinessType::Order,
+            order.get_id(),
+            order.get_frozen_amount(),
+        )
+    }
 
-        Ok(())
+    pub fn apply_trade_fees(&self, trade: &mut Trade) {
+        let (maker_received, taker_received) = match trade.get_maker_order_side() {
+            OrderSide::Ask => (trade.get_amount(), trade.get_quantity()),
+            OrderSide::Bid => (trade.get_quantity(), trade.get_amount()),
+        };
+
+        let maker_fee_rate = self
+            .fee_service
+            .get_effective_rates(
+                trade.get_maker_order().get_user_id(),
+                self.quote_asset_id,
+                self.fee_rates,
+                self.context.timestamp,
+            )
+            .maker_fee_rate;
+        let taker_fee_rate = self
+            .fee_service
+            .get_effective_rates(
+                trade.get_taker_order().get_user_id(),
+                self.quote_asset_id,
+                self.fee_rates,
+                self.context.timestamp,
+            )
+            .taker_fee_rate
+            .max(Decimal::ZERO);
+
+        let mut maker_fee = maker_received * maker_fee_rate;
+
+        if maker_fee.is_sign_negative() {
+            let rebate_asset_id = match trade.get_maker_order_side() {
+                OrderSide::Ask => self.quote_asset_id,
+                OrderSide::Bid => self.base_asset_id,
+            };
+            let collector_balance = self
+                .balance_service
+                .get_balance_status(self.fee_collector_user_id, rebate_asset_id)
+                .available;
+
+            maker_fee = maker_fee.max(-collector_balance);
+        }
+
+        trade.set_fees(maker_fee, taker_received * taker_fee_rate);
+    }
+
+    fn record_trade_volume(&self, trade: &Trade) {
+        let timestamp = self.context.timestamp;
+
+        self.fee_service.record_volume(
+            trade.get_maker_order().get_user_id(),
+            self.quote_asset_id,
+            trade.get_amount(),
+            timestamp,
+        );
+        self.fee_service.record_volume(
+            trade.get_taker_order().get_user_id(),
+            self.quote_asset_id,
+            trade.get_amount(),
+            timestamp,
+        );
     }
 
-    pub fn transfer_trade_balance(&self, trade: &Trade) -> AppResult<()> {
+    pub fn get_user_fee_status(&self, user_id: UserId, now: Timestamp) -> UserFeeStatus {
+        self.fee_service
+            .get_user_fee_status(user_id, self.quote_asset_id, self.fee_rates, now)
+    }
+
+    pub fn transfer_trade_balance(&self, trade: &Trade) -> AppResult<Vec<ChangeBalanceOutput>> {
         let bid_order = trade.get_bid_order();
         let ask_order = trade.get_ask_order();
 
@@ -108,47 +276,90 @@ impl Market {
             OrderSide::Bid => true,
         };
 
-        self.balance_service.change_balance(
-            bid_order.get_user_id(),
-            bid_order.get_base_asset_id(),
-            BusinessType::Trade,
-            trade.get_id(),
-            BalanceType::Available,
-            trade.get_quantity(),
-        )?;
+        let taker_balance_type = match trade.get_taker_order().get_limit_price() {
+            Some(_) => BalanceType::Available,
+            None => BalanceType::Frozen,
+        };
 
-        self.balance_service.change_balance(
-            bid_order.get_user_id(),
-            bid_order.get_quote_asset_id(),
-            BusinessType::Trade,
-            trade.get_id(),
-            match is_maker_order_bid {
+        let mut deltas: Vec<BalanceDelta> = vec![];
+
+        for (asset_id, fee) in [
+            (bid_order.get_base_asset_id(), trade.get_bid_fee()),
+            (ask_order.get_quote_asset_id(), trade.get_ask_fee()),
+        ] {
+            if !fee.is_zero() {
+                deltas.push(BalanceDelta {
+                    user_id: self.fee_collector_user_id,
+                    asset_id,
+                    type_: BalanceType::Available,
+                    amount: fee,
+                });
+            }
+        }
+
+        deltas.push(BalanceDelta {
+            user_id: bid_order.get_user_id(),
+            asset_id: bid_order.get_base_asset_id(),
+            type_: BalanceType::Available,
+            amount: trade.get_quantity() - trade.get_bid_fee(),
+        });
+
+        deltas.push(BalanceDelta {
+            user_id: bid_order.get_user_id(),
+            asset_id: bid_order.get_quote_asset_id(),
+            type_: match is_maker_order_bid {
                 true => BalanceType::Frozen,
-                false => BalanceType::Available,
+                false => taker_balance_type,
             },
-            -trade.get_amount(),
-        )?;
+            amount: -trade.get_amount(),
+        });
+
+        deltas.push(BalanceDelta {
+            user_id: ask_order.get_user_id(),
+            asset_id: ask_order.get_quote_asset_id(),
+            type_: BalanceType::Available,
+            amount: trade.get_amount() - trade.get_ask_fee(),
+        });
+
+        deltas.push(BalanceDelta {
+            user_id: ask_order.get_user_id(),
+            asset_id: ask_order.get_base_asset_id(),
+            type_: match is_maker_order_bid {
+                true => taker_balance_type,
+                false => BalanceType::Frozen,
+            },
+            amount: -trade.get_quantity(),
+        });
 
-        self.balance_service.change_balance(
-            ask_order.get_user_id(),
-            ask_order.get_quote_asset_id(),
+        self.balance_service.change_balances(
+            self.context,
             BusinessType::Trade,
             trade.get_id(),
-            BalanceType::Available,
-            trade.get_amount(),
-        )?;
+            &deltas,
+        )
+    }
 
-        self.balance_service.change_balance(
-            ask_order.get_user_id(),
-            ask_order.get_base_asset_id(),
-            BusinessType::Trade,
-            trade.get_id(),
-            match is_maker_order_bid {
-                true => BalanceType::Available,
-                false => BalanceType::Frozen,
-            },
-            -trade.get_quantity(),
-        )?;
+    fn is_multiple_of(value: Decimal, step: Option<Decimal>) -> bool {
+        match step {
+            Some(step) if !step.is_zero() => (value % step).is_zero(),
+            _ => true,
+        }
+    }
+
+    fn check_order_quantity(&self, quantity: OrderQuantity) -> AppResult<()> {
+        if quantity.lt(&self.min_allowed_quantity) {
+            return Err(AppError::MarketMinimumAllowedQuantityExceeds);
+        }
+
+        if let Some(max_quantity) = self.max_quantity {
+            if quantity > max_quantity {
+                return Err(AppError::MarketMaximumAllowedQuantityExceeds);
+            }
+        }
+
+        if !Self::is_multiple_of(quantity, self.quantity_step) {
+            return Err(AppError::OrderQuantityStepMismatch);
+        }
 
         Ok(())
     }
@@ -158,15 +369,67 @@ impl Market {
             return Err(AppError::MarketTradeDisbaled);
         }
 
-        if order.get_quantity().lt(&self.min_allowed_quantity) {
-            return Err(AppError::MarketMinimumAllowedQuantityExceeds);
+        match order.get_quote_amount() {
+            Some(quote_amount) => {
+                if order.get_limit_price().is_some()
+                    || order.get_side() == OrderSide::Ask
+                    || !order.get_quantity().is_zero()
+                    || quote_amount <= Decimal::ZERO
+                {
+                    return Err(AppError::InvalidMarketOrderAmount);
+                }
+            }
+            None => self.check_order_quantity(order.get_quantity())?,
+        }
+
+        for price in [order.get_limit_price(), order.get_stop_price()]
+            .into_iter()
+            .flatten()
+        {
+            if !Self::is_multiple_of(price, self.price_tick) {
+                return Err(AppError::OrderPriceTickMismatch);
+            }
+        }
+
+        let notional = match order.get_amount() {
+            Err(AppError::OrderAmountOverflow) => return Err(AppError::OrderAmountOverflow),
+            result => result.ok(),
+        };
+
+        if let Some(notional) = notional {
+            if self
+                .min_notional
+                .is_some_and(|min_notional| notional < min_notional)
+            {
+                return Err(AppError::MarketMinimumNotionalExceeds);
+            }
+
+            if self
+                .max_notional
+                .is_some_and(|max_notional| notional > max_notional)
+            {
+                return Err(AppError::MarketMaximumNotionalExceeds);
+            }
+        }
+
+        if order.get_post_only().is_some()
+            && (order.get_limit_price().is_none()
+                || order.get_time_in_force() != TimeInForce::GoodTillCancel)
+        {
+            return Err(AppError::PostOnlyOrderInvalid);
+        }
+
+        if let Some(stop_price) = order.get_stop_price() {
+            if stop_price.is_zero() {
+                return Err(AppError::StopOrderInvalidPrice);
+            }
         }
 
         if let Some(limit_price) = order.get_limit_price() {
             if limit_price.is_zero() {
                 return Err(AppError::LimitOrderInvalidPrice);
             }
-        } else {
+        } else if !order.is_pending_stop() {
             match order.get_side() {
                 OrderSide::Ask => {
                     if self.orderbook.is_bids_empty() {
@@ -192,12 +455,14 @@ impl Market {
                 }
             }
             OrderSide::Bid => {
-                if !self.balance_service.is_available_balance_enough(
-                    order.get_user_id(),
-                    order.get_quote_asset_id(),
-                    order.get_amount()?,
-                ) {
-                    return Err(AppError::UserBalanceExceeds);
+                if let Ok(amount) = order.get_amount() {
+                    if !self.balance_service.is_available_balance_enough(
+                        order.get_user_id(),
+                        order.get_quote_asset_id(),
+                        amount,
+                    ) {
+                        return Err(AppError::UserBalanceExceeds);
+                    }
                 }
             }
         }
@@ -207,54 +472,429 @@ impl Market {
 
     pub fn process_new_order(
         &mut self,
-        user_id: UserId,
-        limit_price: Option<OrderPrice>,
-        quantity: OrderQuantity,
-        side: OrderSide,
-    ) -> AppResult<()> {
-        let order = match limit_price {
+        context: CommandContext,
+        new_order: NewOrder,
+    ) -> AppResult<MatchOrderOutput> {
+        self.context = context;
+
+        let order = self.build_order(self.order_id_sequencer.next(), new_order);
+        let result = self.place_order(order);
+
+        if let Err(err) = &result {
+            self.publish_order_event(&order, OrderEventType::Rejected(err.to_string()));
+        }
+
+        self.publish_orderbook_deltas();
+
+        result
+    }
+
+    pub fn check_new_order(&self, new_order: NewOrder) -> AppResult<()> {
+        self.check_new_order_input(&self.build_order(self.order_id_sequencer.get() + 1, new_order))
+    }
+
+    fn build_order(&self, id: OrderId, new_order: NewOrder) -> Order {
+        match new_order.limit_price {
             Some(limit_price) => Order::new_limit(
-                self.order_id_sequencer.next(),
-                user_id,
+                id,
+                new_order.user_id,
                 self.base_asset_id,
                 self.quote_asset_id,
-                side,
+                new_order.side,
                 limit_price,
-                quantity,
+                new_order.quantity,
             ),
             None => Order::new_market(
-                self.order_id_sequencer.next(),
-                user_id,
+                id,
+                new_order.user_id,
                 self.base_asset_id,
                 self.quote_asset_id,
-                side,
-                quantity,
+                new_order.side,
+                new_order.quantity,
             ),
-        };
+        }
+        .with_time_in_force(new_order.time_in_force)
+        .with_post_only(new_order.post_only)
+        .with_stop_price(new_order.stop_price)
+        .with_quote_amount(new_order.quote_amount)
+        .with_self_trade_prevention(
+            new_order
+                .self_trade_prevention
+                .or(self.self_trade_prevention),
+        )
+    }
 
+    fn place_order(&mut self, order: Order) -> AppResult<MatchOrderOutput> {
         self.check_new_order_input(&order)?;
 
-        let match_result = self.orderbook.put_order(order)?;
+        let match_result = match order.is_pending_stop() {
+            true => {
+                let order = self.place_stop_order(order)?;
+
+                self.publish_order_event(&order, OrderEventType::Accepted);
+
+                MatchOrderOutput {
+                    taker_order: order,
+                    filled_orders: vec![],
+                    trades: vec![],
+                    self_trade_cancels: vec![],
+                }
+            }
+            false => self.execute_order(order)?,
+        };
+
+        self.release_triggered_stop_orders();
+
+        Ok(match_result)
+    }
+
+    fn floor_market_order_quantity(&self, quantity: OrderQuantity) -> OrderQuantity {
+        match self.quantity_step {
+            Some(step) if !step.is_zero() => (quantity / step).floor() * step,
+            _ => quantity
+                .round_dp_with_strategy(MARKET_ORDER_QUANTITY_SCALE, RoundingStrategy::ToZero),
+        }
+    }
+
+    fn get_market_bid_cost(
+        &self,
+        order: &Order,
+        max_quantity: Option<OrderQuantity>,
+        quote_budget: Option<OrderAmount>,
+    ) -> (OrderQuantity, OrderAmount) {
+        let mut quantity = Decimal::ZERO;
+        let mut cost = Decimal::ZERO;
+
+        let levels = match order.get_self_trade_prevention() {
+            Some(_) => self
+                .orderbook
+                .get_asks_levels_excluding_user(order.get_user_id()),
+            None => self.orderbook.get_asks_levels(None, None),
+        };
+
+        for level in levels {
+            let mut level_quantity = level.quantity;
+
+            if let Some(max_quantity) = max_quantity {
+                level_quantity = level_quantity.min(max_quantity - quantity);
+            }
+
+            if let Some(quote_budget) = quote_budget {
+                level_quantity = level_quantity
+                    .min(self.floor_market_order_quantity((quote_budget - cost) / level.price));
+            }
+
+            if level_quantity <= Decimal::ZERO {
+                break;
+            }
+
+            quantity += level_quantity;
+            cost += level_quantity * level.price;
+
+            if level_quantity < level.quantity {
+                break;
+            }
+        }
+
+        (quantity, cost)
+    }
+
+    fn reserve_market_order(&self, order: &mut Order) -> AppResult<Decimal> {
+        let reserved_amount = match order.get_side() {
+            OrderSide::Ask => order.get_remaining_quantity(),
+            OrderSide::Bid => match order.get_quote_amount() {
+                Some(quote_amount) => {
+                    let (quantity, cost) =
+                        self.get_market_bid_cost(order, self.max_quantity, Some(quote_amount));
+
+                    if quantity.is_zero() {
+                        return Err(AppError::InvalidMarketOrderAmount);
+                    }
+
+                    self.check_order_quantity(quantity)?;
+                    order.set_quantity(quantity);
+
+                    cost
+                }
+                None => {
+                    self.get_market_bid_cost(order, Some(order.get_remaining_quantity()), None)
+                        .1
+                }
+            },
+        };
+
+        self.balance_service.freeze_balance(
+            self.context,
+            order.get_user_id(),
+            order.get_asset_id(),
+            BusinessType::Order,
+            order.get_id(),
+            reserved_amount,
+        )?;
+
+        Ok(reserved_amount)
+    }
+
+    fn release_market_order_reservation(
+        &self,
+        order: &Order,
+        reserved_amount: Decimal,
+        trades: &[Trade],
+    ) -> AppResult<()> {
+        let spent_amount: Decimal = trades
+            .iter()
+            .map(|trade| match order.get_side() {
+                OrderSide::Ask => trade.get_quantity(),
+                OrderSide::Bid => trade.get_amount(),
+            })
+            .sum();
+
+        let leftover_amount = reserved_amount - spent_amount;
+
+        if leftover_amount > Decimal::ZERO {
+            self.balance_service.unfreeze_balance(
+                self.context,
+                order.get_user_id(),
+                order.get_asset_id(),
+                BusinessType::Order,
+                order.get_id(),
+                leftover_amount,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn execute_order(&mut self, mut order: Order) -> AppResult<MatchOrderOutput> {
+        let reserved_amount = match order.get_limit_price() {
+            Some(_) => None,
+            None => Some(self.reserve_market_order(&mut order)?),
+        };
+
+        let mut match_result = match self.orderbook.put_order(order) {
+            Ok(match_result) => match_result,
+            Err(err) => {
+                if let Some(reserved_amount) = reserved_amount {
+                    self.release_market_order_reservation(&order, reserved_amount, &[])?;
+                }
+
+                return Err(err);
+            }
+        };
+
+        if order.get_stop_price().is_none() {
+            self.publish_order_event(&order, OrderEventType::Accepted);
+        }
+
+        // The book has already matched, so balance failures are reported without failing the order.
+        let mut result = Ok(());
+
+        for trade in &mut match_result.trades {
+            trade.set_id(self.trade_id_sequencer.next(), self.context.timestamp);
+
+            self.apply_trade_fees(trade);
+            result = result.and(self.transfer_trade_balance(trade).map(|_| ()));
+            self.record_trade_volume(trade);
+
+            let event = trade.get_event(self.pair_id);
 
-        for trade in match_result.trades {
-            self.transfer_trade_balance(&trade)?;
+            if let Some(candle_service) = &self.candle_service {
+                candle_service.record_trade(&event);
+            }
+
+            publish(&self.trade_sender, event);
+        }
+
+        if let Some(trade) = match_result.trades.last() {
+            self.last_trade_price = Some(trade.get_price());
+        }
+
+        if let Some(reserved_amount) = reserved_amount {
+            result = result.and(self.release_market_order_reservation(
+                &match_result.taker_order,
+                reserved_amount,
+                &match_result.trades,
+            ));
         }
 
         if !match_result.taker_order.is_closed() && match_result.taker_order.is_bookable() {
-            self.freeze_user_balance(&match_result.taker_order)?;
+            result = result.and(
+                self.freeze_user_balance(&match_result.taker_order)
+                    .map(|_| ()),
+            );
+        }
+
+        for filled_order in &match_result.filled_orders {
+            result = result.and(self.unfreeze_user_balance(filled_order).map(|_| ()));
         }
 
-        for filled_order in match_result.filled_orders {
-            self.unfreeze_user_balance(&filled_order)?;
+        result = result.and(self.release_self_trade_cancels(&match_result));
+
+        for trade in &match_result.trades {
+            self.publish_order_status_event(&trade.get_maker_order());
+        }
+
+        self.publish_order_status_event(&match_result.taker_order);
+
+        if let Err(err) = result {
+            eprintln!(
+                "Settling order {} of market {} failed: {}",
+                match_result.taker_order.get_id(),
+                self.pair_id,
+                err
+            );
+        }
+
+        Ok(match_result)
+    }
+
+    fn release_self_trade_cancels(&self, match_result: &MatchOrderOutput) -> AppResult<()> {
+        for self_trade_cancel in &match_result.self_trade_cancels {
+            let order = self_trade_cancel.order;
+
+            if order.get_id() == match_result.taker_order.get_id() {
+                continue;
+            }
+
+            if !self_trade_cancel.released_amount.is_zero() {
+                self.balance_service.unfreeze_balance(
+                    self.context,
+                    order.get_user_id(),
+                    order.get_asset_id(),
+                    BusinessType::Order,
+                    order.get_id(),
+                    self_trade_cancel.released_amount,
+                )?;
+            }
+
+            if order.is_closed() {
+                self.publish_order_status_event(&order);
+            }
         }
 
         Ok(())
     }
 
-    pub fn get_orderbook_depth(&self) -> (OrderbookDepth, OrderbookDepth) {
-        let asks_depth = self.orderbook.get_asks_depth();
-        let bids_depth = self.orderbook.get_bids_depth();
+    fn place_stop_order(&mut self, mut order: Order) -> AppResult<Order> {
+        order.set_frozen_amount()?;
+
+        self.freeze_user_balance(&order)?;
+        self.conditional_orderbook.insert(order)?;
+
+        Ok(order)
+    }
+
+    fn release_triggered_stop_orders(&mut self) {
+        while let Some(last_trade_price) = self.last_trade_price {
+            let triggered_orders = self
+                .conditional_orderbook
+                .take_triggered_orders(last_trade_price);
+
+            if triggered_orders.is_empty() {
+                break;
+            }
+
+            for mut order in triggered_orders {
+                if let Err(err) = self.unfreeze_user_balance(&order) {
+                    self.publish_order_event(&order, OrderEventType::Rejected(err.to_string()));
+
+                    continue;
+                }
+
+                order.trigger();
+
+                let execution_result = self
+                    .check_new_order_input(&order)
+                    .and_then(|_| self.execute_order(order));
+
+                if let Err(err) = execution_result {
+                    self.publish_order_event(&order, OrderEventType::Rejected(err.to_string()));
+                }
+            }
+        }
+    }
+
+    pub fn check_cancel_order(&self, user_id: UserId, order_id: OrderId) -> AppResult<Order> {
+        let order = self
+            .orderbook
+            .get_order(order_id)
+            .or_else(|| self.conditional_orderbook.get_order(order_id))
+            .ok_or(AppError::OrderIdNotFound)?;
+
+        if order.get_user_id() != user_id {
+            return Err(AppError::OrderUserMismatch);
+        }
+
+        Ok(*order)
+    }
+
+    pub fn cancel_order(
+        &mut self,
+        context: CommandContext,
+        user_id: UserId,
+        order_id: OrderId,
+    ) -> AppResult<Order> {
+        self.context = context;
+
+        let order = self.check_cancel_order(user_id, order_id)?;
+
+        let cancelled_order = match order.is_pending_stop() {
+            true => self.conditional_orderbook.cancel_order(order_id)?,
+            false => self.orderbook.cancel_order(order_id)?,
+        };
+
+        self.publish_orderbook_deltas();
+        self.unfreeze_user_balance(&cancelled_order)?;
+        self.publish_order_event(&cancelled_order, OrderEventType::Cancelled);
+
+        Ok(cancelled_order)
+    }
+
+    pub fn get_orderbook_state(
+        &self,
+        limit: Option<usize>,
+        price_group: Option<OrderPrice>,
+    ) -> OrderbookState {
+        OrderbookState {
+            sequence: self.orderbook.get_sequence(),
+            asks: self.orderbook.get_asks_levels(limit, price_group),
+            bids: self.orderbook.get_bids_levels(limit, price_group),
+        }
+    }
+
+    pub fn get_ticker(&self, now: Timestamp) -> Ticker {
+        let interval = CandleInterval::OneMinute;
+        let close_time = interval.get_open_time(now) + interval.get_duration() - 1;
+        let open_time = (close_time + 1).saturating_sub(TICKER_WINDOW);
+
+        let window = self.candle_service.as_ref().and_then(|candle_service| {
+            candle_service.get_merged_candle(self.pair_id, interval, open_time, close_time)
+        });
+
+        Ticker::new(
+            self.pair_id,
+            self.last_trade_price,
+            self.orderbook.get_bids_levels(Some(1), None).pop(),
+            self.orderbook.get_asks_levels(Some(1), None).pop(),
+            window,
+            open_time,
+            close_time,
+        )
+    }
+
+    pub fn get_snapshot(&self) -> MarketSnapshot {
+        MarketSnapshot {
+            orderbook: self.orderbook.clone(),
+            conditional_orderbook: self.conditional_orderbook.clone(),
+            last_trade_price: self.last_trade_price,
+            order_id_sequence: self.order_id_sequencer.get(),
+        }
+    }
 
-        (asks_depth, bids_depth)
+    pub fn restore_snapshot(&mut self, snapshot: MarketSnapshot) {
+        self.orderbook = snapshot.orderbook.with_price_tick(self.price_tick);
+        self.conditional_orderbook = snapshot.conditional_orderbook;
+        self.last_trade_price = snapshot.last_trade_price;
+        self.order_id_sequencer.set(snapshot.order_id_sequence);
     }
 }
@@ -1,3 +1,4 @@
+pub mod conditional_orderbook;
 pub mod market;
 pub mod order;
 pub mod orderbook;
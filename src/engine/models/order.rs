@@ -1,4 +1,5 @@
 use rust_decimal::{prelude::Zero, Decimal};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     balance::{AssetId, UserId},
@@ -10,19 +11,40 @@ pub type OrderPrice = Decimal;
 pub type OrderQuantity = Decimal;
 pub type OrderAmount = Decimal;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum OrderType {
     Limit { price: OrderPrice },
     Market,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OrderSide {
     Ask,
     Bid,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeInForce {
+    GoodTillCancel,
+    ImmediateOrCancel,
+    FillOrKill,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PostOnlyMode {
+    Reject,
+    Reprice,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SelfTradePreventionMode {
+    CancelNewest,
+    CancelOldest,
+    CancelBoth,
+    DecrementAndCancel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OrderStatus {
     Open,
     PartiallyFilled,
@@ -31,7 +53,22 @@ pub enum OrderStatus {
     Filled,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct NewOrder {
+    pub user_id: UserId,
+    pub side: OrderSide,
+    pub limit_price: Option<OrderPrice>,
+    pub quantity: OrderQuantity,
+    pub time_in_force: TimeInForce,
+    pub post_only: Option<PostOnlyMode>,
+    pub stop_price: Option<OrderPrice>,
+    #[serde(default)]
+    pub quote_amount: Option<OrderAmount>,
+    #[serde(default)]
+    pub self_trade_prevention: Option<SelfTradePreventionMode>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Order {
     id: OrderId,
     user_id: UserId,
@@ -39,6 +76,14 @@ pub struct Order {
     quote_asset_id: AssetId,
     type_: OrderType,
     side: OrderSide,
+    time_in_force: TimeInForce,
+    post_only: Option<PostOnlyMode>,
+    stop_price: Option<OrderPrice>,
+    is_triggered: bool,
+    #[serde(default)]
+    quote_amount: Option<OrderAmount>,
+    #[serde(default)]
+    self_trade_prevention: Option<SelfTradePreventionMode>,
     quantity: Decimal,
     filled_quantity: Decimal,
     frozen_amount: Decimal,
@@ -62,6 +107,12 @@ impl Order {
             quote_asset_id,
             type_: OrderType::Limit { price: limit_price },
             side,
+            time_in_force: TimeInForce::GoodTillCancel,
+            post_only: None,
+            stop_price: None,
+            is_triggered: false,
+            quote_amount: None,
+            self_trade_prevention: None,
             quantity,
             filled_quantity: Decimal::zero(),
             frozen_amount: Decimal::zero(),
@@ -84,6 +135,12 @@ impl Order {
             quote_asset_id,
             type_: OrderType::Market,
             side,
+            time_in_force: TimeInForce::GoodTillCancel,
+            post_only: None,
+            stop_price: None,
+            is_triggered: false,
+            quote_amount: None,
+            self_trade_prevention: None,
             quantity,
             filled_quantity: Decimal::zero(),
             frozen_amount: Decimal::zero(),
@@ -91,6 +148,34 @@ impl Order {
         }
     }
 
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn with_post_only(mut self, post_only: Option<PostOnlyMode>) -> Self {
+        self.post_only = post_only;
+        self
+    }
+
+    pub fn with_stop_price(mut self, stop_price: Option<OrderPrice>) -> Self {
+        self.stop_price = stop_price;
+        self
+    }
+
+    pub fn with_quote_amount(mut self, quote_amount: Option<OrderAmount>) -> Self {
+        self.quote_amount = quote_amount;
+        self
+    }
+
+    pub fn with_self_trade_prevention(
+        mut self,
+        self_trade_prevention: Option<SelfTradePreventionMode>,
+    ) -> Self {
+        self.self_trade_prevention = self_trade_prevention;
+        self
+    }
+
     pub fn get_user_id(&self) -> UserId {
         self.user_id
     }
@@ -125,6 +210,47 @@ impl Order {
         self.side
     }
 
+    pub fn get_time_in_force(&self) -> TimeInForce {
+        self.time_in_force
+    }
+
+    pub fn get_post_only(&self) -> Option<PostOnlyMode> {
+        self.post_only
+    }
+
+    pub fn get_stop_price(&self) -> Option<OrderPrice> {
+        self.stop_price
+    }
+
+    pub fn get_quote_amount(&self) -> Option<OrderAmount> {
+        self.quote_amount
+    }
+
+    pub fn get_self_trade_prevention(&self) -> Option<SelfTradePreventionMode> {
+        self.self_trade_prevention
+    }
+
+    pub fn is_pending_stop(&self) -> bool {
+        self.stop_price.is_some() && !self.is_triggered
+    }
+
+    pub fn trigger(&mut self) {
+        self.is_triggered = true;
+        self.frozen_amount = Decimal::zero();
+    }
+
+    pub fn reprice(&mut self, limit_price: OrderPrice) {
+        self.type_ = OrderType::Limit { price: limit_price };
+    }
+
+    pub fn get_status(&self) -> OrderStatus {
+        self.status
+    }
+
+    pub fn get_filled_quantity(&self) -> OrderQuantity {
+        self.filled_quantity
+    }
+
     pub fn get_remaining_quantity(&self) -> OrderQuantity {
         self.quantity - self.filled_quantity
     }
@@ -133,12 +259,23 @@ impl Order {
         self.quantity
     }
 
+    pub fn set_quantity(&mut self, quantity: OrderQuantity) {
+        self.quantity = quantity;
+    }
+
     pub fn get_amount(&self) -> AppResult<OrderAmount> {
-        let limit_price = self
+        if let Some(quote_amount) = self.quote_amount {
+            return Ok(quote_amount);
+        }
+
+        let price = self
             .get_limit_price()
+            .or(self.stop_price)
             .ok_or(AppError::InvalidMarketOrderAmount)?;
 
-        Ok(self.get_quantity() * limit_price)
+        self.get_quantity()
+            .checked_mul(price)
+            .ok_or(AppError::OrderAmountOverflow)
     }
 
     pub fn get_traded_quantity(&self, matched_order: &Order) -> OrderQuantity {
@@ -161,17 +298,35 @@ impl Order {
         Ok(())
     }
 
-    pub fn is_closed(&self) -> bool {
-        match self.status {
-            OrderStatus::Cancelled | OrderStatus::Closed | OrderStatus::Filled => true,
-            _ => false,
+    pub fn cancel(&mut self) {
+        self.status = OrderStatus::Cancelled;
+    }
+
+    pub fn decrement(&mut self, quantity: OrderQuantity) -> AppResult<()> {
+        if quantity > self.get_remaining_quantity() {
+            return Err(AppError::OrderOverFilled);
+        }
+
+        if quantity == self.get_remaining_quantity() {
+            self.cancel();
+        } else {
+            self.quantity -= quantity;
         }
+
+        Ok(())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(
+            self.status,
+            OrderStatus::Cancelled | OrderStatus::Closed | OrderStatus::Filled
+        )
     }
 
     pub fn is_bookable(&self) -> bool {
         match self.type_ {
-            OrderType::Limit { .. } => true,
-            OrderType::Market { .. } => false,
+            OrderType::Limit { .. } => self.time_in_force == TimeInForce::GoodTillCancel,
+            OrderType::Market => false,
         }
     }
 
@@ -187,7 +342,9 @@ impl Order {
                     .get_limit_price()
                     .ok_or(AppError::OrderInavlidFrozenAmount)?;
 
-                self.frozen_amount -= traded_quantity * limit_price
+                self.frozen_amount -= traded_quantity
+                    .checked_mul(limit_price)
+                    .ok_or(AppError::OrderAmountOverflow)?
             }
         }
 
@@ -198,11 +355,19 @@ impl Order {
         match self.get_side() {
             OrderSide::Ask => self.frozen_amount = self.get_remaining_quantity(),
             OrderSide::Bid => {
-                let limit_price = self
-                    .get_limit_price()
-                    .ok_or(AppError::OrderInavlidFrozenAmount)?;
-
-                self.frozen_amount = self.get_remaining_quantity() * limit_price
+                self.frozen_amount = match self.quote_amount {
+                    Some(quote_amount) => quote_amount,
+                    None => {
+                        let price = self
+                            .get_limit_price()
+                            .or(self.stop_price)
+                            .ok_or(AppError::OrderInavlidFrozenAmount)?;
+
+                        self.get_remaining_quantity()
+                            .checked_mul(price)
+                            .ok_or(AppError::OrderAmountOverflow)?
+                    }
+                }
             }
         }
 
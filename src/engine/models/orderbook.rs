@@ -1,15 +1,23 @@
 use super::{
-    order::{Order, OrderId, OrderPrice, OrderQuantity, OrderSide},
+    order::{
+        Order, OrderId, OrderPrice, OrderQuantity, OrderSide, PostOnlyMode,
+        SelfTradePreventionMode, TimeInForce,
+    },
     trade::Trade,
 };
-use crate::common::errors::{AppError, AppResult};
+use crate::{
+    balance::UserId,
+    common::errors::{AppError, AppResult},
+};
 use rust_decimal::{prelude::Zero, Decimal};
+use serde::{Deserialize, Serialize};
 use std::{
     cmp::Reverse,
     collections::{btree_map::Entry, BTreeMap, HashMap, VecDeque},
     ops::{Deref, DerefMut},
 };
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct OrderbookWrapper<T>(T);
 
 impl<T> Deref for OrderbookWrapper<T> {
@@ -100,7 +108,7 @@ impl OrderbookWrapper<BTreeMap<Reverse<OrderPrice>, PriceLevel>> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PriceLevel {
     order_ids: VecDeque<OrderId>,
     quantity: OrderQuantity,
@@ -160,10 +168,33 @@ pub type AsksOrderbook = OrderbookWrapper<BTreeMap<OrderPrice, PriceLevel>>;
 pub type BidsOrderbook = OrderbookWrapper<BTreeMap<Reverse<OrderPrice>, PriceLevel>>;
 pub type OrdersIndex = HashMap<OrderId, Order>;
 
+pub type BookSequence = u64;
+
+#[derive(Debug, Clone)]
+pub struct OrderbookDelta {
+    pub sequence: BookSequence,
+    pub side: OrderSide,
+    pub price: OrderPrice,
+    pub quantity: OrderQuantity,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Orderbook {
     asks: AsksOrderbook,
     bids: BidsOrderbook,
     orders: OrdersIndex,
+    #[serde(skip)]
+    price_tick: Option<OrderPrice>,
+    #[serde(default)]
+    sequence: BookSequence,
+    #[serde(skip)]
+    changed_price_levels: Vec<(OrderSide, OrderPrice)>,
+}
+
+impl Default for Orderbook {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Orderbook {
@@ -172,9 +203,17 @@ impl Orderbook {
             asks: OrderbookWrapper(BTreeMap::new()),
             bids: OrderbookWrapper(BTreeMap::new()),
             orders: HashMap::new(),
+            price_tick: None,
+            sequence: 0,
+            changed_price_levels: vec![],
         }
     }
 
+    pub fn with_price_tick(mut self, price_tick: Option<OrderPrice>) -> Self {
+        self.price_tick = price_tick;
+        self
+    }
+
     pub fn remove_drained_orderbook_level(&mut self, order: &Order) {
         match order.get_side() {
             OrderSide::Ask => {
@@ -189,6 +228,7 @@ impl Orderbook {
     pub fn match_bid_order(&mut self, mut taker_order: Order) -> AppResult<MatchOrderOutput> {
         let mut trades: Vec<Trade> = vec![];
         let mut filled_orders: Vec<Order> = vec![];
+        let mut self_trade_cancels: Vec<SelfTradeCancel> = vec![];
         let mut drained_price_levels = 0;
 
         for (_, price_level) in self.asks.iter_mut() {
@@ -196,8 +236,12 @@ impl Orderbook {
                 break;
             }
 
+            self.changed_price_levels
+                .push((OrderSide::Ask, price_level.price));
+
             let mut total_traded_quantity = Decimal::zero();
-            let mut filled_orders_count = 0;
+            let mut total_cancelled_quantity = Decimal::zero();
+            let mut closed_orders_count = 0;
 
             for order_id in price_level.order_ids.iter_mut() {
                 if taker_order.is_closed() {
@@ -206,15 +250,30 @@ impl Orderbook {
 
                 let maker_order = self
                     .orders
-                    .get_mut(&order_id)
+                    .get_mut(order_id)
                     .ok_or(AppError::OrderMatchNotFound)?;
 
-                let traded_quantity = taker_order.get_traded_quantity(&maker_order);
+                if let Some(mode) = Self::get_self_trade_prevention(&taker_order, maker_order) {
+                    total_cancelled_quantity += Self::prevent_self_trade(
+                        mode,
+                        &mut taker_order,
+                        maker_order,
+                        &mut self_trade_cancels,
+                    )?;
+
+                    if maker_order.is_closed() {
+                        closed_orders_count += 1;
+                    }
+
+                    continue;
+                }
+
+                let traded_quantity = taker_order.get_traded_quantity(maker_order);
 
                 taker_order.fill(traded_quantity)?;
                 maker_order.fill(traded_quantity)?;
 
-                let trade = Trade::new(&taker_order, &maker_order, traded_quantity)?;
+                let trade = Trade::new(&taker_order, maker_order, traded_quantity)?;
 
                 trades.push(trade);
 
@@ -223,14 +282,14 @@ impl Orderbook {
                 maker_order.decrease_frozen_amount(traded_quantity)?;
 
                 if maker_order.is_closed() {
-                    filled_orders_count += 1;
-                    filled_orders.push(maker_order.clone());
+                    closed_orders_count += 1;
+                    filled_orders.push(*maker_order);
                 }
             }
 
-            price_level.quantity -= total_traded_quantity;
+            price_level.quantity -= total_traded_quantity + total_cancelled_quantity;
 
-            for _ in 0..filled_orders_count {
+            for _ in 0..closed_orders_count {
                 price_level
                     .pop_front_order_id()
                     .and_then(|order_id| self.orders.remove(&order_id));
@@ -250,18 +309,23 @@ impl Orderbook {
 
             self.bids.insert(&taker_order)?;
             self.orders.insert(taker_order.get_id(), taker_order);
+            self.track_price_level_change(&taker_order);
+        } else if !taker_order.is_closed() {
+            taker_order.cancel();
         }
 
         Ok(MatchOrderOutput {
-            taker_order: taker_order.clone(),
+            taker_order,
             filled_orders,
             trades,
+            self_trade_cancels,
         })
     }
 
     pub fn match_ask_order(&mut self, mut taker_order: Order) -> AppResult<MatchOrderOutput> {
         let mut trades: Vec<Trade> = vec![];
         let mut filled_orders: Vec<Order> = vec![];
+        let mut self_trade_cancels: Vec<SelfTradeCancel> = vec![];
         let mut drained_price_levels = 0;
 
         for (_, price_level) in self.bids.iter_mut() {
@@ -269,8 +333,12 @@ impl Orderbook {
                 break;
             }
 
+            self.changed_price_levels
+                .push((OrderSide::Bid, price_level.price));
+
             let mut total_traded_quantity = Decimal::zero();
-            let mut filled_orders_count = 0;
+            let mut total_cancelled_quantity = Decimal::zero();
+            let mut closed_orders_count = 0;
 
             for order_id in price_level.order_ids.iter_mut() {
                 if taker_order.is_closed() {
@@ -279,15 +347,30 @@ impl Orderbook {
 
                 let maker_order = self
                     .orders
-                    .get_mut(&order_id)
+                    .get_mut(order_id)
                     .ok_or(AppError::OrderMatchNotFound)?;
 
-                let traded_quantity = taker_order.get_traded_quantity(&maker_order);
+                if let Some(mode) = Self::get_self_trade_prevention(&taker_order, maker_order) {
+                    total_cancelled_quantity += Self::prevent_self_trade(
+                        mode,
+                        &mut taker_order,
+                        maker_order,
+                        &mut self_trade_cancels,
+                    )?;
+
+                    if maker_order.is_closed() {
+                        closed_orders_count += 1;
+                    }
+
+                    continue;
+                }
+
+                let traded_quantity = taker_order.get_traded_quantity(maker_order);
 
                 taker_order.fill(traded_quantity)?;
                 maker_order.fill(traded_quantity)?;
 
-                let trade = Trade::new(&taker_order, &maker_order, traded_quantity)?;
+                let trade = Trade::new(&taker_order, maker_order, traded_quantity)?;
 
                 trades.push(trade);
 
@@ -296,14 +379,14 @@ impl Orderbook {
                 maker_order.decrease_frozen_amount(traded_quantity)?;
 
                 if maker_order.is_closed() {
-                    filled_orders_count += 1;
-                    filled_orders.push(maker_order.clone())
+                    closed_orders_count += 1;
+                    filled_orders.push(*maker_order)
                 }
             }
 
-            price_level.quantity -= total_traded_quantity;
+            price_level.quantity -= total_traded_quantity + total_cancelled_quantity;
 
-            for _ in 0..filled_orders_count {
+            for _ in 0..closed_orders_count {
                 price_level
                     .pop_front_order_id()
                     .and_then(|order_id| self.orders.remove(&order_id));
@@ -323,19 +406,29 @@ impl Orderbook {
 
             self.asks.insert(&taker_order)?;
             self.orders.insert(taker_order.get_id(), taker_order);
+            self.track_price_level_change(&taker_order);
+        } else if !taker_order.is_closed() {
+            taker_order.cancel();
         }
 
         Ok(MatchOrderOutput {
-            taker_order: taker_order.clone(),
+            taker_order,
             filled_orders,
             trades,
+            self_trade_cancels,
         })
     }
 
-    pub fn put_order(&mut self, order: Order) -> AppResult<MatchOrderOutput> {
-        // if self.orders.contains_key(&order.get_id()) {
-        //     return Err(AppError::OrderIdDuplication)
-        // }
+    pub fn put_order(&mut self, mut order: Order) -> AppResult<MatchOrderOutput> {
+        if order.get_time_in_force() == TimeInForce::FillOrKill
+            && self.get_matchable_quantity(&order) < order.get_remaining_quantity()
+        {
+            return Err(AppError::FillOrKillOrderNotFilled);
+        }
+
+        if let Some(post_only) = order.get_post_only() {
+            self.apply_post_only(&mut order, post_only)?;
+        }
 
         match order.get_side() {
             OrderSide::Ask => {
@@ -351,8 +444,115 @@ impl Orderbook {
         }
     }
 
-    pub fn cancel_order(&mut self, order_id: OrderId) -> AppResult<()> {
-        let order = self
+    fn get_self_trade_prevention(
+        taker_order: &Order,
+        maker_order: &Order,
+    ) -> Option<SelfTradePreventionMode> {
+        if taker_order.get_user_id() != maker_order.get_user_id() {
+            return None;
+        }
+
+        taker_order.get_self_trade_prevention()
+    }
+
+    fn prevent_self_trade(
+        mode: SelfTradePreventionMode,
+        taker_order: &mut Order,
+        maker_order: &mut Order,
+        self_trade_cancels: &mut Vec<SelfTradeCancel>,
+    ) -> AppResult<OrderQuantity> {
+        let (taker_quantity, maker_quantity) = match mode {
+            SelfTradePreventionMode::CancelNewest => {
+                (taker_order.get_remaining_quantity(), Decimal::zero())
+            }
+            SelfTradePreventionMode::CancelOldest => {
+                (Decimal::zero(), maker_order.get_remaining_quantity())
+            }
+            SelfTradePreventionMode::CancelBoth => (
+                taker_order.get_remaining_quantity(),
+                maker_order.get_remaining_quantity(),
+            ),
+            SelfTradePreventionMode::DecrementAndCancel => {
+                let quantity = taker_order.get_traded_quantity(maker_order);
+
+                (quantity, quantity)
+            }
+        };
+
+        if !taker_quantity.is_zero() {
+            taker_order.decrement(taker_quantity)?;
+
+            self_trade_cancels.push(SelfTradeCancel {
+                order: *taker_order,
+                quantity: taker_quantity,
+                released_amount: Decimal::zero(),
+            });
+        }
+
+        if !maker_quantity.is_zero() {
+            let frozen_amount = maker_order.get_frozen_amount();
+
+            maker_order.decrease_frozen_amount(maker_quantity)?;
+            maker_order.decrement(maker_quantity)?;
+
+            self_trade_cancels.push(SelfTradeCancel {
+                order: *maker_order,
+                quantity: maker_quantity,
+                released_amount: frozen_amount - maker_order.get_frozen_amount(),
+            });
+        }
+
+        Ok(maker_quantity)
+    }
+
+    fn apply_post_only(&self, order: &mut Order, post_only: PostOnlyMode) -> AppResult<()> {
+        let limit_price = order
+            .get_limit_price()
+            .ok_or(AppError::PostOnlyOrderInvalid)?;
+
+        let best_counter_price = match order.get_side() {
+            OrderSide::Ask => self.get_best_bid_price(),
+            OrderSide::Bid => self.get_best_ask_price(),
+        };
+
+        let Some(best_counter_price) = best_counter_price else {
+            return Ok(());
+        };
+
+        let is_crossing = match order.get_side() {
+            OrderSide::Ask => limit_price <= best_counter_price,
+            OrderSide::Bid => limit_price >= best_counter_price,
+        };
+
+        if !is_crossing {
+            return Ok(());
+        }
+
+        match post_only {
+            PostOnlyMode::Reject => Err(AppError::PostOnlyOrderWouldMatch),
+            PostOnlyMode::Reprice => {
+                let price_tick = self
+                    .price_tick
+                    .unwrap_or_else(|| Decimal::new(1, best_counter_price.scale()));
+
+                let repriced_limit_price = match order.get_side() {
+                    OrderSide::Ask => best_counter_price + price_tick,
+                    OrderSide::Bid => best_counter_price - price_tick,
+                };
+
+                if repriced_limit_price <= Decimal::zero() {
+                    return Err(AppError::LimitOrderInvalidPrice);
+                }
+
+                order.reprice(repriced_limit_price);
+
+                Ok(())
+            }
+        }
+    }
+
+    pub fn cancel_order(&mut self, order_id: OrderId) -> AppResult<Order> {
+        let mut order = self
             .orders
             .remove(&order_id)
             .ok_or(AppError::OrderIdNotFound)?;
@@ -362,7 +562,113 @@ impl Orderbook {
             OrderSide::Bid => self.bids.remove(&order)?,
         }
 
-        Ok(())
+        self.track_price_level_change(&order);
+
+        order.cancel();
+
+        Ok(order)
+    }
+
+    pub fn get_order(&self, order_id: OrderId) -> Option<&Order> {
+        self.orders.get(&order_id)
+    }
+
+    fn track_price_level_change(&mut self, order: &Order) {
+        if let Some(limit_price) = order.get_limit_price() {
+            self.changed_price_levels
+                .push((order.get_side(), limit_price));
+        }
+    }
+
+    pub fn get_price_level_quantity(&self, side: OrderSide, price: OrderPrice) -> OrderQuantity {
+        let price_level = match side {
+            OrderSide::Ask => self.asks.get(&price),
+            OrderSide::Bid => self.bids.get(&Reverse(price)),
+        };
+
+        price_level
+            .map(|price_level| price_level.quantity)
+            .unwrap_or_default()
+    }
+
+    pub fn get_sequence(&self) -> BookSequence {
+        self.sequence
+    }
+
+    pub fn take_deltas(&mut self) -> Vec<OrderbookDelta> {
+        let changed_price_levels = std::mem::take(&mut self.changed_price_levels);
+        let mut deltas: Vec<OrderbookDelta> = vec![];
+
+        let mut seen_price_levels: Vec<(OrderSide, OrderPrice)> = vec![];
+
+        for (side, price) in changed_price_levels {
+            if seen_price_levels.contains(&(side, price)) {
+                continue;
+            }
+
+            seen_price_levels.push((side, price));
+
+            self.sequence += 1;
+
+            deltas.push(OrderbookDelta {
+                sequence: self.sequence,
+                side,
+                price,
+                quantity: self.get_price_level_quantity(side, price),
+            });
+        }
+
+        deltas
+    }
+
+    pub fn get_matchable_quantity(&self, order: &Order) -> OrderQuantity {
+        match order.get_side() {
+            OrderSide::Ask => self.sum_matchable_quantity(self.bids.values(), order),
+            OrderSide::Bid => self.sum_matchable_quantity(self.asks.values(), order),
+        }
+    }
+
+    fn sum_matchable_quantity<'a>(
+        &self,
+        price_levels: impl Iterator<Item = &'a PriceLevel>,
+        order: &Order,
+    ) -> OrderQuantity {
+        let mut matchable_quantity = Decimal::zero();
+
+        for price_level in price_levels {
+            if !price_level.is_matches(order)
+                || matchable_quantity >= order.get_remaining_quantity()
+            {
+                break;
+            }
+
+            let Some(mode) = order.get_self_trade_prevention() else {
+                matchable_quantity += price_level.quantity;
+                continue;
+            };
+
+            for maker_order in price_level
+                .order_ids
+                .iter()
+                .filter_map(|order_id| self.orders.get(order_id))
+            {
+                if maker_order.get_user_id() != order.get_user_id() {
+                    matchable_quantity += maker_order.get_remaining_quantity();
+                } else if mode != SelfTradePreventionMode::CancelOldest {
+                    return matchable_quantity;
+                }
+            }
+        }
+
+        matchable_quantity
+    }
+
+    pub fn get_best_ask_price(&self) -> Option<OrderPrice> {
+        self.asks.keys().next().copied()
+    }
+
+    pub fn get_best_bid_price(&self) -> Option<OrderPrice> {
+        self.bids.keys().next().map(|price| price.0)
     }
 
     pub fn is_asks_empty(&self) -> bool {
@@ -373,31 +679,130 @@ impl Orderbook {
         self.bids.is_empty()
     }
 
-    pub fn get_asks_depth(&self) -> OrderbookDepth {
-        let depth: Vec<[Decimal; 2]> = self
-            .asks
-            .iter()
-            .map(|(price, level)| [price.clone(), level.quantity.clone()])
-            .collect();
+    pub fn get_asks_levels(
+        &self,
+        limit: Option<usize>,
+        price_group: Option<OrderPrice>,
+    ) -> Vec<DepthLevel> {
+        Self::collect_depth_levels(self.asks.values(), OrderSide::Ask, limit, price_group)
+    }
 
-        depth
+    pub fn get_bids_levels(
+        &self,
+        limit: Option<usize>,
+        price_group: Option<OrderPrice>,
+    ) -> Vec<DepthLevel> {
+        Self::collect_depth_levels(self.bids.values(), OrderSide::Bid, limit, price_group)
     }
 
-    pub fn get_bids_depth(&self) -> OrderbookDepth {
-        let depth: Vec<[Decimal; 2]> = self
-            .bids
-            .iter()
-            .map(|(price, level)| [price.0.clone(), level.quantity.clone()])
-            .collect();
+    pub fn get_asks_levels_excluding_user(&self, user_id: UserId) -> Vec<DepthLevel> {
+        let mut depth_levels: Vec<DepthLevel> = vec![];
+
+        for price_level in self.asks.values() {
+            let orders = price_level
+                .order_ids
+                .iter()
+                .filter_map(|order_id| self.orders.get(order_id))
+                .filter(|order| order.get_user_id() != user_id);
+
+            let mut depth_level = DepthLevel {
+                price: price_level.price,
+                quantity: Decimal::zero(),
+                order_count: 0,
+            };
+
+            for order in orders {
+                depth_level.quantity += order.get_remaining_quantity();
+                depth_level.order_count += 1;
+            }
+
+            if depth_level.order_count > 0 {
+                depth_levels.push(depth_level);
+            }
+        }
 
-        depth
+        depth_levels
     }
+
+    fn collect_depth_levels<'a>(
+        price_levels: impl Iterator<Item = &'a PriceLevel>,
+        side: OrderSide,
+        limit: Option<usize>,
+        price_group: Option<OrderPrice>,
+    ) -> Vec<DepthLevel> {
+        let mut depth_levels: Vec<DepthLevel> = vec![];
+
+        for price_level in price_levels {
+            let price = match price_group {
+                Some(price_group) => Self::group_price(price_level.price, side, price_group),
+                None => price_level.price,
+            };
+
+            if let Some(depth_level) = depth_levels.last_mut() {
+                if depth_level.price == price {
+                    depth_level.quantity += price_level.quantity;
+                    depth_level.order_count += price_level.order_ids.len();
+
+                    continue;
+                }
+            }
+
+            if limit.is_some_and(|limit| depth_levels.len() >= limit) {
+                break;
+            }
+
+            depth_levels.push(DepthLevel {
+                price,
+                quantity: price_level.quantity,
+                order_count: price_level.order_ids.len(),
+            });
+        }
+
+        depth_levels
+    }
+
+    fn group_price(price: OrderPrice, side: OrderSide, price_group: OrderPrice) -> OrderPrice {
+        let buckets = price / price_group;
+
+        match side {
+            OrderSide::Ask => buckets.ceil() * price_group,
+            OrderSide::Bid => buckets.floor() * price_group,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthLevel {
+    pub price: OrderPrice,
+    pub quantity: OrderQuantity,
+    pub order_count: usize,
 }
 
-pub type OrderbookDepth = Vec<[Decimal; 2]>;
+#[derive(Debug, Clone, Copy)]
+pub struct SelfTradeCancel {
+    pub order: Order,
+    pub quantity: OrderQuantity,
+    pub released_amount: Decimal,
+}
 
 pub struct MatchOrderOutput {
     pub taker_order: Order,
     pub filled_orders: Vec<Order>,
     pub trades: Vec<Trade>,
+    pub self_trade_cancels: Vec<SelfTradeCancel>,
+}
+
+impl MatchOrderOutput {
+    pub fn get_average_price(&self) -> Option<OrderPrice> {
+        let traded_quantity: OrderQuantity =
+            self.trades.iter().map(|trade| trade.get_quantity()).sum();
+
+        if traded_quantity.is_zero() {
+            return None;
+        }
+
+        let traded_amount: Decimal = self.trades.iter().map(|trade| trade.get_amount()).sum();
+
+        Some(traded_amount / traded_quantity)
+    }
 }
@@ -1,11 +1,27 @@
-use rust_decimal::Decimal;
+use rust_decimal::{prelude::Zero, Decimal};
 
-use crate::common::errors::{AppError, AppResult};
+use crate::common::{
+    errors::{AppError, AppResult},
+    time::Timestamp,
+};
 
-use super::order::{Order, OrderPrice, OrderQuantity, OrderSide};
+use super::{
+    market::PairId,
+    order::{Order, OrderPrice, OrderQuantity, OrderSide},
+};
 
 pub type TradeId = u64;
 
+#[derive(Debug, Clone)]
+pub struct TradeEvent {
+    pub pair_id: PairId,
+    pub trade_id: TradeId,
+    pub price: OrderPrice,
+    pub quantity: OrderQuantity,
+    pub taker_side: OrderSide,
+    pub created_at: Timestamp,
+}
+
 #[derive(Debug)]
 pub struct Trade {
     id: TradeId,
@@ -13,6 +29,10 @@ pub struct Trade {
     maker_order: Order,
     price: OrderPrice,
     quantity: OrderQuantity,
+    amount: Decimal,
+    maker_fee: Decimal,
+    taker_fee: Decimal,
+    created_at: Timestamp,
 }
 
 impl Trade {
@@ -24,13 +44,20 @@ impl Trade {
         let price = maker_order
             .get_limit_price()
             .ok_or(AppError::MakerOrderWithoutLimitPrice)?;
+        let amount = traded_quantity
+            .checked_mul(price)
+            .ok_or(AppError::OrderAmountOverflow)?;
 
         Ok(Self {
             id: 0,
-            taker_order: taker_order.clone(),
-            maker_order: maker_order.clone(),
+            taker_order: *taker_order,
+            maker_order: *maker_order,
             price,
             quantity: traded_quantity,
+            amount,
+            maker_fee: Decimal::zero(),
+            taker_fee: Decimal::zero(),
+            created_at: 0,
         })
     }
 
@@ -38,10 +65,38 @@ impl Trade {
         self.id
     }
 
+    pub fn set_id(&mut self, id: TradeId, created_at: Timestamp) {
+        self.id = id;
+        self.created_at = created_at;
+    }
+
+    pub fn get_created_at(&self) -> Timestamp {
+        self.created_at
+    }
+
+    pub fn get_event(&self, pair_id: PairId) -> TradeEvent {
+        TradeEvent {
+            pair_id,
+            trade_id: self.id,
+            price: self.price,
+            quantity: self.quantity,
+            taker_side: self.taker_order.get_side(),
+            created_at: self.created_at,
+        }
+    }
+
     pub fn get_price(&self) -> OrderPrice {
         self.price
     }
 
+    pub fn get_maker_order(&self) -> Order {
+        self.maker_order
+    }
+
+    pub fn get_taker_order(&self) -> Order {
+        self.taker_order
+    }
+
     pub fn get_maker_order_side(&self) -> OrderSide {
         self.maker_order.get_side()
     }
@@ -65,6 +120,33 @@ impl Trade {
     }
 
     pub fn get_amount(&self) -> Decimal {
-        self.quantity * self.price
+        self.amount
+    }
+
+    pub fn set_fees(&mut self, maker_fee: Decimal, taker_fee: Decimal) {
+        self.maker_fee = maker_fee;
+        self.taker_fee = taker_fee;
+    }
+
+    pub fn get_maker_fee(&self) -> Decimal {
+        self.maker_fee
+    }
+
+    pub fn get_taker_fee(&self) -> Decimal {
+        self.taker_fee
+    }
+
+    pub fn get_bid_fee(&self) -> Decimal {
+        match self.taker_order.get_side() {
+            OrderSide::Ask => self.maker_fee,
+            OrderSide::Bid => self.taker_fee,
+        }
+    }
+
+    pub fn get_ask_fee(&self) -> Decimal {
+        match self.taker_order.get_side() {
+            OrderSide::Ask => self.taker_fee,
+            OrderSide::Bid => self.maker_fee,
+        }
     }
 }
@@ -1,80 +1,371 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
-    vec,
-};
+use std::{collections::HashMap, sync::Arc};
 
-use rust_decimal::{prelude::Zero, Decimal};
+use rust_decimal::Decimal;
+use tokio::sync::{broadcast, oneshot};
 
 use crate::{
-    balance::{service::BalanceService, UserId},
+    balance::{
+        service::{BalanceService, BusinessId, ChangeBalanceOutput},
+        AssetId, UserId,
+    },
+    candle::{service::CandleService, Candle, CandleInterval},
     common::{
         errors::{AppError, AppResult},
         sequencer::Sequencer,
+        time::{Time, Timestamp},
     },
     config::Config,
+    fee::{service::FeeService, FeeRates, UserFeeStatus},
+    journal::{service::JournalService, Command, JournalEntry, JournalSequence},
+    notification::service::NotificationService,
+    snapshot::{EngineSnapshot, SNAPSHOT_VERSION},
+    ticker::Ticker,
 };
 
-use super::models::{
-    market::{Market, PairId},
-    order::{OrderPrice, OrderSide},
-    orderbook::OrderbookDepth,
+use super::{
+    actor::{MarketCommand, MarketHandle},
+    models::{
+        market::{Market, OrderbookState, PairId},
+        order::{NewOrder, Order, OrderId, OrderPrice},
+        orderbook::{MatchOrderOutput, OrderbookDelta},
+        trade::TradeEvent,
+    },
 };
 
-pub type Markets = HashMap<PairId, Market>;
+pub type Markets = HashMap<PairId, MarketHandle>;
 
 pub struct EngineService {
-    markets: RwLock<Markets>,
+    markets: Markets,
+    trade_id_sequencer: Arc<Sequencer>,
     balance_service: Arc<BalanceService>,
+    fee_service: Arc<FeeService>,
+    journal_service: Arc<JournalService>,
+    notification_service: Arc<NotificationService>,
+    candle_service: Arc<CandleService>,
 }
 
 impl EngineService {
-    pub fn new(balance_service: Arc<BalanceService>) -> Self {
+    pub fn new(
+        balance_service: Arc<BalanceService>,
+        fee_service: Arc<FeeService>,
+        journal_service: Arc<JournalService>,
+        notification_service: Arc<NotificationService>,
+        candle_service: Arc<CandleService>,
+    ) -> Self {
         Self {
-            markets: RwLock::new(HashMap::new()),
+            markets: HashMap::new(),
+            trade_id_sequencer: Arc::new(Sequencer::new()),
             balance_service,
+            fee_service,
+            journal_service,
+            notification_service,
+            candle_service,
         }
     }
 
     pub fn insert_markets_from_config(&mut self, config: &Config) {
-        let mut write_guard = self.markets.try_write().unwrap();
-
         for market_config in &config.markets {
             let market = Market::new(
-                market_config.base_asset_id,
-                market_config.quote_asset_id,
-                market_config.is_market_trade_enabled,
-                market_config.min_allowed_quantity,
+                market_config,
                 self.balance_service.clone(),
+                self.fee_service.clone(),
                 Arc::new(Sequencer::new()),
+                self.trade_id_sequencer.clone(),
+            )
+            .with_notifications(self.notification_service.clone())
+            .with_candles(self.candle_service.clone());
+
+            self.markets.insert(
+                market_config.pair_id,
+                MarketHandle::spawn(market_config.pair_id, market, self.journal_service.clone()),
             );
+        }
+    }
+
+    fn get_market(&self, pair_id: PairId) -> AppResult<&MarketHandle> {
+        self.markets.get(&pair_id).ok_or(AppError::MarketNotFound)
+    }
 
-            write_guard.insert(market_config.pair_id, market);
+    pub async fn get_market_orderbook_state(
+        &self,
+        pair_id: PairId,
+        limit: Option<usize>,
+        price_group: Option<OrderPrice>,
+    ) -> AppResult<OrderbookState> {
+        self.get_market(pair_id)?
+            .request(|reply| MarketCommand::GetOrderbookState {
+                limit,
+                price_group,
+                reply,
+            })
+            .await
+    }
+
+    // Markets stay paused until the journal lock is taken, so every state matches one sequence.
+    pub fn create_snapshot(&self) -> AppResult<EngineSnapshot> {
+        let mut markets = HashMap::new();
+        let mut resumes = Vec::with_capacity(self.markets.len());
+
+        for (pair_id, market) in &self.markets {
+            let (resume, paused) = oneshot::channel();
+            let snapshot = market.blocking_request(|reply| MarketCommand::GetSnapshot {
+                reply,
+                resume: paused,
+            })?;
+
+            markets.insert(*pair_id, snapshot);
+            resumes.push(resume);
         }
+
+        self.journal_service.with_lock(|journal_sequence| {
+            Ok(EngineSnapshot {
+                version: SNAPSHOT_VERSION,
+                journal_sequence,
+                markets,
+                trade_id_sequence: self.trade_id_sequencer.get(),
+                balances: self.balance_service.get_all_balances(),
+                business_outputs: self.balance_service.get_business_outputs(),
+                fees: self.fee_service.get_state(),
+                candles: self.candle_service.get_state(),
+            })
+        })
     }
 
-    pub fn get_market_orderbook(&self, pair_id: PairId) -> (OrderbookDepth, OrderbookDepth) {
-        if let Some(market) = self.markets.try_read().unwrap().get(&pair_id) {
-            return market.get_orderbook_depth();
+    pub fn restore_snapshot(&self, snapshot: EngineSnapshot) -> AppResult<()> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(AppError::SnapshotFailed(format!(
+                "unsupported snapshot version {}",
+                snapshot.version
+            )));
+        }
+
+        for (pair_id, market_snapshot) in snapshot.markets {
+            self.get_market(pair_id)?
+                .blocking_request(|reply| MarketCommand::RestoreSnapshot {
+                    snapshot: Box::new(market_snapshot),
+                    reply,
+                })?;
+        }
+
+        self.trade_id_sequencer.set(snapshot.trade_id_sequence);
+        self.balance_service.restore_balances(snapshot.balances);
+        self.balance_service
+            .restore_business_outputs(snapshot.business_outputs);
+        self.fee_service.restore_state(snapshot.fees);
+        self.candle_service.restore_state(snapshot.candles);
+        self.journal_service.set_sequence(snapshot.journal_sequence);
+
+        Ok(())
+    }
+
+    pub fn truncate_journal(&self, journal_sequence: JournalSequence) -> AppResult<()> {
+        self.journal_service.truncate(journal_sequence)
+    }
+
+    pub fn replay_journal(&self) -> AppResult<()> {
+        self.journal_service.replay(|entry| self.apply_entry(entry))
+    }
+
+    fn apply_entry(&self, entry: JournalEntry) -> AppResult<()> {
+        let context = entry.get_context();
+
+        match entry.command {
+            Command::Deposit {
+                user_id,
+                asset_id,
+                business_id,
+                amount,
+            } => self
+                .balance_service
+                .deposit(context, user_id, asset_id, business_id, amount)
+                .map(|_| ()),
+            Command::Withdraw {
+                user_id,
+                asset_id,
+                business_id,
+                amount,
+            } => self
+                .balance_service
+                .withdraw(context, user_id, asset_id, business_id, amount)
+                .map(|_| ()),
+            Command::SetFeeOverride { user_id, rates } => {
+                self.fee_service.set_override(user_id, rates);
+
+                Ok(())
+            }
+            Command::PlaceOrder { pair_id, .. } | Command::CancelOrder { pair_id, .. } => self
+                .get_market(pair_id)?
+                .blocking_request(|reply| MarketCommand::Replay { entry, reply })?,
+        }
+    }
+
+    pub fn deposit(
+        &self,
+        user_id: UserId,
+        asset_id: AssetId,
+        business_id: BusinessId,
+        amount: Decimal,
+    ) -> AppResult<ChangeBalanceOutput> {
+        let command = Command::Deposit {
+            user_id,
+            asset_id,
+            business_id,
+            amount,
+        };
+
+        if let Some(output) =
+            self.balance_service
+                .check_deposit(user_id, asset_id, business_id, amount)?
+        {
+            return Ok(output);
+        }
+
+        self.journal_service.execute(command, |context| {
+            self.balance_service
+                .deposit(context, user_id, asset_id, business_id, amount)
+        })
+    }
+
+    pub fn withdraw(
+        &self,
+        user_id: UserId,
+        asset_id: AssetId,
+        business_id: BusinessId,
+        amount: Decimal,
+    ) -> AppResult<ChangeBalanceOutput> {
+        let command = Command::Withdraw {
+            user_id,
+            asset_id,
+            business_id,
+            amount,
+        };
+
+        if let Some(output) =
+            self.balance_service
+                .check_withdraw(user_id, asset_id, business_id, amount)?
+        {
+            return Ok(output);
         }
 
-        (vec![], vec![])
+        self.journal_service.execute(command, |context| {
+            self.balance_service
+                .withdraw(context, user_id, asset_id, business_id, amount)
+        })
+    }
+
+    pub fn set_fee_override(&self, user_id: UserId, rates: Option<FeeRates>) -> AppResult<()> {
+        let command = Command::SetFeeOverride { user_id, rates };
+
+        self.journal_service.execute(command, |_| {
+            self.fee_service.set_override(user_id, rates);
+
+            Ok(())
+        })
     }
 
-    pub fn place_order(
+    pub async fn place_order(
+        &self,
+        pair_id: PairId,
+        new_order: NewOrder,
+    ) -> AppResult<MatchOrderOutput> {
+        self.get_market(pair_id)?
+            .request(|reply| MarketCommand::PlaceOrder { new_order, reply })
+            .await?
+    }
+
+    pub async fn cancel_order(
         &self,
         pair_id: PairId,
         user_id: UserId,
-        limit_price: Option<OrderPrice>,
-        quantity: Decimal,
-        side: OrderSide,
-    ) -> AppResult<()> {
-        if let Some(market) = self.markets.try_write().unwrap().get_mut(&pair_id) {
-            market.process_new_order(user_id, limit_price, quantity, side)?;
-
-            return Ok(());
+        order_id: OrderId,
+    ) -> AppResult<Order> {
+        self.get_market(pair_id)?
+            .request(|reply| MarketCommand::CancelOrder {
+                user_id,
+                order_id,
+                reply,
+            })
+            .await?
+    }
+
+    pub async fn get_user_fee_status(
+        &self,
+        pair_id: PairId,
+        user_id: UserId,
+    ) -> AppResult<UserFeeStatus> {
+        let now = Time::get_current_timestamp();
+
+        self.get_market(pair_id)?
+            .request(|reply| MarketCommand::GetUserFeeStatus {
+                user_id,
+                now,
+                reply,
+            })
+            .await
+    }
+
+    pub async fn subscribe_trades(
+        &self,
+        pair_id: PairId,
+    ) -> AppResult<broadcast::Receiver<TradeEvent>> {
+        self.get_market(pair_id)?
+            .request(|reply| MarketCommand::SubscribeTrades { reply })
+            .await
+    }
+
+    pub async fn subscribe_orderbook(
+        &self,
+        pair_id: PairId,
+    ) -> AppResult<(OrderbookState, broadcast::Receiver<OrderbookDelta>)> {
+        self.get_market(pair_id)?
+            .request(|reply| MarketCommand::SubscribeOrderbook { reply })
+            .await
+    }
+
+    pub fn get_candles(
+        &self,
+        pair_id: PairId,
+        interval: CandleInterval,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> AppResult<Vec<Candle>> {
+        self.get_market(pair_id)?;
+
+        Ok(self.candle_service.get_candles(pair_id, interval, from, to))
+    }
+
+    pub fn subscribe_candles(
+        &self,
+        pair_id: PairId,
+        interval: CandleInterval,
+    ) -> AppResult<broadcast::Receiver<Candle>> {
+        self.get_market(pair_id)?;
+
+        Ok(self.candle_service.subscribe(pair_id, interval))
+    }
+
+    pub async fn get_ticker(&self, pair_id: PairId) -> AppResult<Ticker> {
+        let now = Time::get_current_timestamp();
+
+        self.get_market(pair_id)?
+            .request(|reply| MarketCommand::GetTicker { now, reply })
+            .await
+    }
+
+    pub async fn get_all_tickers(&self) -> AppResult<Vec<Ticker>> {
+        let now = Time::get_current_timestamp();
+        let mut tickers = Vec::with_capacity(self.markets.len());
+
+        for market in self.markets.values() {
+            tickers.push(
+                market
+                    .request(|reply| MarketCommand::GetTicker { now, reply })
+                    .await?,
+            );
         }
 
-        Err(AppError::MarketNotFound)
+        tickers.sort_by_key(|ticker| ticker.pair_id);
+
+        Ok(tickers)
     }
 }
@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::balance::UserId;
+
+use self::service::AssetVolumes;
+
+pub mod service;
+
+pub type FeeTierLevel = u32;
+pub type FeeRate = Decimal;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeRates {
+    pub maker_fee_rate: FeeRate,
+    pub taker_fee_rate: FeeRate,
+}
+
+#[derive(Debug)]
+pub struct UserFeeStatus {
+    pub tier_level: Option<FeeTierLevel>,
+    pub is_overridden: bool,
+    pub rolling_volume: Decimal,
+    pub rates: FeeRates,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FeeState {
+    pub volumes: HashMap<UserId, AssetVolumes>,
+    pub overrides: HashMap<UserId, FeeRates>,
+}
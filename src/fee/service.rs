@@ -0,0 +1,151 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::RwLock,
+};
+
+use rust_decimal::{prelude::Zero, Decimal};
+
+use crate::{
+    balance::{AssetId, UserId},
+    common::time::Timestamp,
+    config::FeeTierConfig,
+};
+
+use super::{FeeRates, FeeState, UserFeeStatus};
+
+const DAY_MILLIS: Timestamp = 24 * 60 * 60 * 1000;
+const ROLLING_VOLUME_DAYS: Timestamp = 30;
+
+pub type DailyVolumes = BTreeMap<Timestamp, Decimal>;
+pub type AssetVolumes = HashMap<AssetId, DailyVolumes>;
+
+pub struct FeeService {
+    tiers: Vec<FeeTierConfig>,
+    volumes: RwLock<HashMap<UserId, AssetVolumes>>,
+    overrides: RwLock<HashMap<UserId, FeeRates>>,
+}
+
+impl FeeService {
+    pub fn new(mut tiers: Vec<FeeTierConfig>) -> Self {
+        tiers.sort_by_key(|tier| tier.min_volume);
+
+        Self {
+            tiers,
+            volumes: RwLock::new(HashMap::new()),
+            overrides: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn record_volume(
+        &self,
+        user_id: UserId,
+        asset_id: AssetId,
+        notional: Decimal,
+        timestamp: Timestamp,
+    ) {
+        let day = timestamp / DAY_MILLIS;
+        let mut volumes = self.volumes.write().unwrap();
+        let daily_volumes = volumes
+            .entry(user_id)
+            .or_default()
+            .entry(asset_id)
+            .or_default();
+
+        *daily_volumes.entry(day).or_insert(Decimal::zero()) += notional;
+
+        let first_rolling_day = day.saturating_sub(ROLLING_VOLUME_DAYS - 1);
+        *daily_volumes = daily_volumes.split_off(&first_rolling_day);
+    }
+
+    pub fn get_rolling_volume(
+        &self,
+        user_id: UserId,
+        asset_id: AssetId,
+        timestamp: Timestamp,
+    ) -> Decimal {
+        let day = timestamp / DAY_MILLIS;
+        let first_rolling_day = day.saturating_sub(ROLLING_VOLUME_DAYS - 1);
+
+        self.volumes
+            .read()
+            .unwrap()
+            .get(&user_id)
+            .and_then(|asset_volumes| asset_volumes.get(&asset_id))
+            .map(|daily_volumes| {
+                daily_volumes
+                    .range(first_rolling_day..)
+                    .map(|(_, v)| v)
+                    .sum()
+            })
+            .unwrap_or(Decimal::zero())
+    }
+
+    pub fn set_override(&self, user_id: UserId, rates: Option<FeeRates>) {
+        let mut overrides = self.overrides.write().unwrap();
+
+        match rates {
+            Some(rates) => overrides.insert(user_id, rates),
+            None => overrides.remove(&user_id),
+        };
+    }
+
+    pub fn get_user_fee_status(
+        &self,
+        user_id: UserId,
+        asset_id: AssetId,
+        default_rates: FeeRates,
+        now: Timestamp,
+    ) -> UserFeeStatus {
+        let rolling_volume = self.get_rolling_volume(user_id, asset_id, now);
+
+        let tier = self
+            .tiers
+            .iter()
+            .rev()
+            .find(|tier| tier.min_volume <= rolling_volume);
+
+        if let Some(rates) = self.overrides.read().unwrap().get(&user_id) {
+            return UserFeeStatus {
+                tier_level: tier.map(|tier| tier.level),
+                is_overridden: true,
+                rolling_volume,
+                rates: *rates,
+            };
+        }
+
+        UserFeeStatus {
+            tier_level: tier.map(|tier| tier.level),
+            is_overridden: false,
+            rolling_volume,
+            rates: tier
+                .map(|tier| FeeRates {
+                    maker_fee_rate: tier.maker_fee_rate.min(default_rates.maker_fee_rate),
+                    taker_fee_rate: tier.taker_fee_rate.min(default_rates.taker_fee_rate),
+                })
+                .unwrap_or(default_rates),
+        }
+    }
+
+    pub fn get_effective_rates(
+        &self,
+        user_id: UserId,
+        asset_id: AssetId,
+        default_rates: FeeRates,
+        now: Timestamp,
+    ) -> FeeRates {
+        self.get_user_fee_status(user_id, asset_id, default_rates, now)
+            .rates
+    }
+
+    pub fn get_state(&self) -> FeeState {
+        FeeState {
+            volumes: self.volumes.read().unwrap().clone(),
+            overrides: self.overrides.read().unwrap().clone(),
+        }
+    }
+
+    pub fn restore_state(&self, state: FeeState) {
+        *self.volumes.write().unwrap() = state.volumes;
+        *self.overrides.write().unwrap() = state.overrides;
+    }
+}
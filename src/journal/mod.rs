@@ -0,0 +1,84 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    balance::{service::BusinessId, AssetId, UserId},
+    common::{
+        errors::{AppError, AppResult},
+        time::Timestamp,
+    },
+    engine::models::{
+        market::PairId,
+        order::{NewOrder, OrderId},
+    },
+    fee::FeeRates,
+};
+
+pub mod repositories;
+pub mod service;
+
+pub type JournalSequence = u64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Command {
+    Deposit {
+        user_id: UserId,
+        asset_id: AssetId,
+        business_id: BusinessId,
+        amount: Decimal,
+    },
+    Withdraw {
+        user_id: UserId,
+        asset_id: AssetId,
+        business_id: BusinessId,
+        amount: Decimal,
+    },
+    PlaceOrder {
+        pair_id: PairId,
+        new_order: NewOrder,
+    },
+    CancelOrder {
+        pair_id: PairId,
+        user_id: UserId,
+        order_id: OrderId,
+    },
+    SetFeeOverride {
+        user_id: UserId,
+        rates: Option<FeeRates>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub sequence: JournalSequence,
+    pub created_at: Timestamp,
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CommandContext {
+    pub journal_sequence: JournalSequence,
+    pub timestamp: Timestamp,
+}
+
+impl JournalEntry {
+    pub fn get_context(&self) -> CommandContext {
+        CommandContext {
+            journal_sequence: self.sequence,
+            timestamp: self.created_at,
+        }
+    }
+}
+
+pub fn catch_command_panic<T>(apply: impl FnOnce() -> AppResult<T>) -> AppResult<T> {
+    panic::catch_unwind(AssertUnwindSafe(apply)).unwrap_or(Err(AppError::CommandPanicked))
+}
+
+pub trait JournalSourceExector: Send + Sync {
+    fn append(&self, entry: &JournalEntry) -> AppResult<()>;
+    fn sync(&self) -> AppResult<()>;
+    fn read_all(&self) -> AppResult<Vec<JournalEntry>>;
+    fn truncate(&self, sequence: JournalSequence) -> AppResult<()>;
+}
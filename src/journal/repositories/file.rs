@@ -0,0 +1,111 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    common::{
+        errors::{AppError, AppResult},
+        jsonl::read_and_truncate_torn_tail,
+    },
+    journal::{JournalEntry, JournalSequence, JournalSourceExector},
+};
+
+pub struct FileJournalManager {
+    path: PathBuf,
+    file: Mutex<Arc<File>>,
+}
+
+impl FileJournalManager {
+    pub fn open(path: &str) -> AppResult<Self> {
+        let file = Self::open_file(path)?;
+
+        read_and_truncate_torn_tail::<JournalEntry>(&file)
+            .map_err(|err| AppError::JournalFailed(err.to_string()))?;
+
+        Ok(Self {
+            path: PathBuf::from(path),
+            file: Mutex::new(Arc::new(file)),
+        })
+    }
+
+    fn open_file(path: impl AsRef<Path>) -> AppResult<File> {
+        OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| AppError::JournalFailed(err.to_string()))
+    }
+}
+
+impl JournalSourceExector for FileJournalManager {
+    fn append(&self, entry: &JournalEntry) -> AppResult<()> {
+        let mut line =
+            serde_json::to_vec(entry).map_err(|err| AppError::JournalFailed(err.to_string()))?;
+        line.push(b'\n');
+
+        (&**self.file.lock().unwrap())
+            .write_all(&line)
+            .map_err(|err| AppError::JournalFailed(err.to_string()))
+    }
+
+    fn sync(&self) -> AppResult<()> {
+        let file = self.file.lock().unwrap().clone();
+
+        file.sync_data()
+            .map_err(|err| AppError::JournalFailed(err.to_string()))
+    }
+
+    fn read_all(&self) -> AppResult<Vec<JournalEntry>> {
+        let file =
+            File::open(&self.path).map_err(|err| AppError::JournalFailed(err.to_string()))?;
+        let mut entries = vec![];
+
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|err| AppError::JournalFailed(err.to_string()))?;
+
+            let Ok(entry) = serde_json::from_str::<JournalEntry>(&line) else {
+                break;
+            };
+
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+
+    fn truncate(&self, sequence: JournalSequence) -> AppResult<()> {
+        let mut file = self.file.lock().unwrap();
+
+        let entries = self.read_all()?;
+        let temp_path = self.path.with_extension("tmp");
+
+        let temp_file =
+            File::create(&temp_path).map_err(|err| AppError::JournalFailed(err.to_string()))?;
+        let mut writer = BufWriter::new(temp_file);
+
+        for entry in entries.iter().filter(|entry| entry.sequence > sequence) {
+            serde_json::to_writer(&mut writer, entry)
+                .map_err(|err| AppError::JournalFailed(err.to_string()))?;
+            writer
+                .write_all(b"\n")
+                .map_err(|err| AppError::JournalFailed(err.to_string()))?;
+        }
+
+        writer
+            .into_inner()
+            .map_err(|err| AppError::JournalFailed(err.to_string()))?
+            .sync_all()
+            .map_err(|err| AppError::JournalFailed(err.to_string()))?;
+
+        fs::rename(&temp_path, &self.path)
+            .map_err(|err| AppError::JournalFailed(err.to_string()))?;
+
+        *file = Arc::new(Self::open_file(&self.path)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::Mutex;
+
+use crate::{
+    common::errors::AppResult,
+    journal::{JournalEntry, JournalSequence, JournalSourceExector},
+};
+
+pub struct MemoryJournalManager {
+    entries: Mutex<Vec<JournalEntry>>,
+}
+
+impl Default for MemoryJournalManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryJournalManager {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(vec![]),
+        }
+    }
+}
+
+impl JournalSourceExector for MemoryJournalManager {
+    fn append(&self, entry: &JournalEntry) -> AppResult<()> {
+        self.entries.lock().unwrap().push(entry.clone());
+
+        Ok(())
+    }
+
+    fn sync(&self) -> AppResult<()> {
+        Ok(())
+    }
+
+    fn read_all(&self) -> AppResult<Vec<JournalEntry>> {
+        Ok(self.entries.lock().unwrap().clone())
+    }
+
+    fn truncate(&self, sequence: JournalSequence) -> AppResult<()> {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|entry| entry.sequence > sequence);
+
+        Ok(())
+    }
+}
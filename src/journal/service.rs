@@ -0,0 +1,106 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, RwLock,
+};
+
+use crate::common::{errors::AppResult, sequencer::Sequencer, time::Time};
+
+use super::{
+    catch_command_panic, Command, CommandContext, JournalEntry, JournalSequence,
+    JournalSourceExector,
+};
+
+pub type JournalSource = Box<dyn JournalSourceExector>;
+
+pub struct JournalService {
+    source: Arc<JournalSource>,
+    sequencer: Sequencer,
+    lock: Mutex<()>,
+    apply_gate: RwLock<()>,
+    is_replaying: AtomicBool,
+}
+
+impl JournalService {
+    pub fn new(source: Arc<JournalSource>) -> Self {
+        Self {
+            source,
+            sequencer: Sequencer::new(),
+            lock: Mutex::new(()),
+            apply_gate: RwLock::new(()),
+            is_replaying: AtomicBool::new(false),
+        }
+    }
+
+    pub fn get_sequence(&self) -> JournalSequence {
+        self.sequencer.get()
+    }
+
+    pub fn set_sequence(&self, sequence: JournalSequence) {
+        self.sequencer.set(sequence);
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.is_replaying.load(Ordering::Relaxed)
+    }
+
+    pub fn with_lock<T>(&self, f: impl FnOnce(JournalSequence) -> T) -> T {
+        let _applied = self.apply_gate.write().unwrap();
+        let _guard = self.lock.lock().unwrap();
+
+        f(self.sequencer.get())
+    }
+
+    pub fn execute<T>(
+        &self,
+        command: Command,
+        apply: impl FnOnce(CommandContext) -> AppResult<T>,
+    ) -> AppResult<T> {
+        let _applying = self.apply_gate.read().unwrap();
+
+        let context = {
+            let _guard = self.lock.lock().unwrap();
+
+            let entry = JournalEntry {
+                sequence: self.sequencer.next(),
+                created_at: Time::get_current_timestamp(),
+                command,
+            };
+
+            self.source.append(&entry)?;
+
+            entry.get_context()
+        };
+
+        self.source.sync()?;
+
+        catch_command_panic(|| apply(context))
+    }
+
+    pub fn truncate(&self, sequence: JournalSequence) -> AppResult<()> {
+        let _guard = self.lock.lock().unwrap();
+
+        self.source.truncate(sequence)
+    }
+
+    pub fn replay(&self, mut apply: impl FnMut(JournalEntry) -> AppResult<()>) -> AppResult<()> {
+        let _guard = self.lock.lock().unwrap();
+
+        let entries = self.source.read_all()?;
+
+        self.is_replaying.store(true, Ordering::Relaxed);
+
+        for entry in entries {
+            if entry.sequence <= self.sequencer.get() {
+                continue;
+            }
+
+            self.sequencer.set(entry.sequence);
+
+            let _ = catch_command_panic(|| apply(entry));
+        }
+
+        self.is_replaying.store(false, Ordering::Relaxed);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    balance::{
+        service::{BusinessType, ChangeBalanceOutput},
+        AssetId, UserId,
+    },
+    common::{errors::AppResult, time::Timestamp},
+    journal::JournalSequence,
+};
+
+pub mod repositories;
+pub mod service;
+
+pub type LedgerEntryId = u64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub id: LedgerEntryId,
+    pub journal_sequence: JournalSequence,
+    pub change: ChangeBalanceOutput,
+}
+
+#[derive(Debug, Clone)]
+pub struct LedgerFilter {
+    pub user_id: UserId,
+    pub asset_id: Option<AssetId>,
+    pub business_type: Option<BusinessType>,
+    pub start_time: Option<Timestamp>,
+    pub end_time: Option<Timestamp>,
+    pub after_id: LedgerEntryId,
+    pub limit: usize,
+}
+
+impl LedgerFilter {
+    pub fn is_matches(&self, entry: &LedgerEntry) -> bool {
+        let change = &entry.change;
+
+        entry.id > self.after_id
+            && change.user_id == self.user_id
+            && self
+                .asset_id
+                .is_none_or(|asset_id| change.asset_id == asset_id)
+            && self
+                .business_type
+                .is_none_or(|business_type| change.business_type == business_type)
+            && self
+                .start_time
+                .is_none_or(|start_time| change.created_at >= start_time)
+            && self
+                .end_time
+                .is_none_or(|end_time| change.created_at <= end_time)
+    }
+}
+
+pub trait LedgerSourceExector: Send + Sync {
+    fn append(&self, entry: &LedgerEntry) -> AppResult<()>;
+    fn query(&self, filter: &LedgerFilter) -> AppResult<Vec<LedgerEntry>>;
+    fn get_last_entry(&self) -> AppResult<Option<LedgerEntry>>;
+    fn get_journal_sequence_counts(
+        &self,
+        after: JournalSequence,
+    ) -> AppResult<HashMap<JournalSequence, usize>>;
+}
@@ -0,0 +1,72 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::Write,
+    sync::Mutex,
+};
+
+use crate::{
+    common::{
+        errors::{AppError, AppResult},
+        jsonl::read_and_truncate_torn_tail,
+    },
+    journal::JournalSequence,
+    ledger::{LedgerEntry, LedgerFilter, LedgerSourceExector},
+};
+
+use super::memory::MemoryLedgerManager;
+
+pub struct FileLedgerManager {
+    file: Mutex<File>,
+    index: MemoryLedgerManager,
+}
+
+impl FileLedgerManager {
+    pub fn open(path: &str) -> AppResult<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| AppError::LedgerFailed(err.to_string()))?;
+
+        let entries = read_and_truncate_torn_tail::<LedgerEntry>(&file)
+            .map_err(|err| AppError::LedgerFailed(err.to_string()))?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+            index: MemoryLedgerManager::from_entries(entries),
+        })
+    }
+}
+
+impl LedgerSourceExector for FileLedgerManager {
+    fn append(&self, entry: &LedgerEntry) -> AppResult<()> {
+        let mut line =
+            serde_json::to_vec(entry).map_err(|err| AppError::LedgerFailed(err.to_string()))?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().unwrap();
+
+        file.write_all(&line)
+            .and_then(|_| file.sync_data())
+            .map_err(|err| AppError::LedgerFailed(err.to_string()))?;
+
+        self.index.append(entry)
+    }
+
+    fn query(&self, filter: &LedgerFilter) -> AppResult<Vec<LedgerEntry>> {
+        self.index.query(filter)
+    }
+
+    fn get_last_entry(&self) -> AppResult<Option<LedgerEntry>> {
+        self.index.get_last_entry()
+    }
+
+    fn get_journal_sequence_counts(
+        &self,
+        after: JournalSequence,
+    ) -> AppResult<HashMap<JournalSequence, usize>> {
+        self.index.get_journal_sequence_counts(after)
+    }
+}
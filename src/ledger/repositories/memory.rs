@@ -0,0 +1,71 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use crate::{
+    common::errors::AppResult,
+    journal::JournalSequence,
+    ledger::{LedgerEntry, LedgerFilter, LedgerSourceExector},
+};
+
+pub struct MemoryLedgerManager {
+    entries: RwLock<Vec<LedgerEntry>>,
+}
+
+impl Default for MemoryLedgerManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryLedgerManager {
+    pub fn new() -> Self {
+        Self {
+            entries: RwLock::new(vec![]),
+        }
+    }
+
+    pub fn from_entries(entries: Vec<LedgerEntry>) -> Self {
+        Self {
+            entries: RwLock::new(entries),
+        }
+    }
+}
+
+impl LedgerSourceExector for MemoryLedgerManager {
+    fn append(&self, entry: &LedgerEntry) -> AppResult<()> {
+        self.entries.write().unwrap().push(entry.clone());
+
+        Ok(())
+    }
+
+    fn query(&self, filter: &LedgerFilter) -> AppResult<Vec<LedgerEntry>> {
+        let entries = self.entries.read().unwrap();
+
+        let start = entries.partition_point(|entry| entry.id <= filter.after_id);
+
+        Ok(entries[start..]
+            .iter()
+            .filter(|entry| filter.is_matches(entry))
+            .take(filter.limit)
+            .cloned()
+            .collect())
+    }
+
+    fn get_last_entry(&self) -> AppResult<Option<LedgerEntry>> {
+        Ok(self.entries.read().unwrap().last().cloned())
+    }
+
+    fn get_journal_sequence_counts(
+        &self,
+        after: JournalSequence,
+    ) -> AppResult<HashMap<JournalSequence, usize>> {
+        let mut counts = HashMap::new();
+
+        for entry in self.entries.read().unwrap().iter() {
+            if entry.journal_sequence > after {
+                *counts.entry(entry.journal_sequence).or_default() += 1;
+            }
+        }
+
+        Ok(counts)
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    balance::service::ChangeBalanceOutput,
+    common::{errors::AppResult, sequencer::Sequencer},
+    journal::{service::JournalService, JournalSequence},
+};
+
+use super::{LedgerEntry, LedgerFilter, LedgerSourceExector};
+
+pub type LedgerSource = Box<dyn LedgerSourceExector>;
+
+pub struct LedgerService {
+    source: Arc<LedgerSource>,
+    sequencer: Sequencer,
+    journal_service: Arc<JournalService>,
+    replay_counts: Mutex<Option<HashMap<JournalSequence, usize>>>,
+}
+
+impl LedgerService {
+    pub fn new(source: Arc<LedgerSource>, journal_service: Arc<JournalService>) -> AppResult<Self> {
+        let sequencer = Sequencer::new();
+
+        if let Some(last_entry) = source.get_last_entry()? {
+            sequencer.set(last_entry.id);
+        }
+
+        Ok(Self {
+            source,
+            sequencer,
+            journal_service,
+            replay_counts: Mutex::new(None),
+        })
+    }
+
+    // Replay records a command's changes in the same order, so recorded ones are skipped.
+    fn is_recorded(&self, journal_sequence: JournalSequence) -> AppResult<bool> {
+        let mut replay_counts = self.replay_counts.lock().unwrap();
+
+        if replay_counts.is_none() {
+            *replay_counts = Some(
+                self.source
+                    .get_journal_sequence_counts(journal_sequence.saturating_sub(1))?,
+            );
+        }
+
+        match replay_counts.as_mut().unwrap().get_mut(&journal_sequence) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    pub fn record(
+        &self,
+        journal_sequence: JournalSequence,
+        change: &ChangeBalanceOutput,
+    ) -> AppResult<()> {
+        if self.journal_service.is_replaying() && self.is_recorded(journal_sequence)? {
+            return Ok(());
+        }
+
+        let entry = LedgerEntry {
+            id: self.sequencer.next(),
+            journal_sequence,
+            change: change.clone(),
+        };
+
+        self.source.append(&entry)
+    }
+
+    pub fn query(&self, filter: &LedgerFilter) -> AppResult<Vec<LedgerEntry>> {
+        self.source.query(filter)
+    }
+}
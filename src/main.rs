@@ -1,28 +1,68 @@
-use balance::{service::BusinessType, BalanceType, UserId};
 use config::repositories::toml::TomlConfigManager;
 use container::Container;
-use engine::models::order::OrderSide;
-use presentation::grpc::server::{match_engine::trade_server::TradeServer, TradeController};
-use rust_decimal::Decimal;
+use presentation::grpc::{
+    auth::Authenticator,
+    server::{match_engine::trade_server::TradeServer, TradeController},
+};
+use std::{sync::Arc, time::Duration};
+
 use tonic::transport::Server;
 
 pub mod __tests__;
 pub mod balance;
+pub mod candle;
 pub mod common;
 pub mod config;
 pub mod container;
 pub mod engine;
+pub mod fee;
+pub mod journal;
+pub mod ledger;
+pub mod notification;
 pub mod presentation;
+pub mod snapshot;
+pub mod ticker;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let addr = "0.0.0.0:3000".parse()?;
 
     let config = &TomlConfigManager::from_file("config.test.toml");
-    let container = Container::new(config);
+    let container = tokio::task::block_in_place(|| Container::new(config));
+
+    if let (Some(snapshot_service), Some(interval_secs)) = (
+        container.snapshot_service.clone(),
+        config
+            .snapshot
+            .as_ref()
+            .and_then(|snapshot_config| snapshot_config.interval_secs),
+    ) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                let snapshot_service = snapshot_service.clone();
+
+                match tokio::task::spawn_blocking(move || snapshot_service.take_snapshot()).await {
+                    Ok(Err(err)) => eprintln!("{}", err),
+                    Err(err) => eprintln!("{}", err),
+                    Ok(Ok(_)) => {}
+                }
+            }
+        });
+    }
 
-    let trade_controller =
-        TradeController::new(container.engine_service, container.balance_service);
+    let trade_controller = TradeController::new(
+        container.engine_service,
+        container.balance_service,
+        container.snapshot_service,
+        container.ledger_service,
+        container.notification_service,
+        Arc::new(Authenticator::new(&config.api_keys)),
+    );
 
     Server::builder()
         .add_service(TradeServer::new(trade_controller))
@@ -0,0 +1,30 @@
+use crate::{
+    balance::service::ChangeBalanceOutput,
+    common::time::Timestamp,
+    engine::models::{market::PairId, order::Order},
+};
+
+pub mod service;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderEventType {
+    Accepted,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderEvent {
+    pub pair_id: PairId,
+    pub type_: OrderEventType,
+    pub order: Order,
+    pub created_at: Timestamp,
+}
+
+#[derive(Debug, Clone)]
+pub enum UserEvent {
+    Order(OrderEvent),
+    Balance(ChangeBalanceOutput),
+}
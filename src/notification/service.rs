@@ -0,0 +1,39 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use tokio::sync::broadcast;
+
+use crate::{balance::UserId, common::channel::publish};
+
+use super::UserEvent;
+
+const USER_CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Default)]
+pub struct NotificationService {
+    senders: RwLock<HashMap<UserId, broadcast::Sender<UserEvent>>>,
+}
+
+impl NotificationService {
+    pub fn new() -> Self {
+        Self {
+            senders: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn subscribe(&self, user_id: UserId) -> broadcast::Receiver<UserEvent> {
+        let mut senders = self.senders.write().unwrap();
+
+        senders.retain(|_, sender| sender.receiver_count() > 0);
+
+        senders
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(USER_CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    pub fn publish(&self, user_id: UserId, event: UserEvent) {
+        if let Some(sender) = self.senders.read().unwrap().get(&user_id) {
+            publish(sender, event);
+        }
+    }
+}
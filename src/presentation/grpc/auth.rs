@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use tonic::Request;
+
+use crate::{
+    balance::UserId,
+    common::errors::{AppError, AppResult},
+    config::ApiKeyConfig,
+};
+
+const AUTHORIZATION_HEADER: &str = "authorization";
+const BEARER_PREFIX: &str = "Bearer ";
+
+pub struct Authenticator {
+    api_keys: HashMap<String, ApiKeyConfig>,
+}
+
+impl Authenticator {
+    pub fn new(api_keys: &[ApiKeyConfig]) -> Self {
+        Self {
+            api_keys: api_keys
+                .iter()
+                .map(|api_key| (api_key.key.clone(), api_key.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn authenticate<T>(&self, request: &Request<T>) -> AppResult<UserId> {
+        self.get_api_key(request).map(|api_key| api_key.user_id)
+    }
+
+    pub fn authenticate_admin<T>(&self, request: &Request<T>) -> AppResult<UserId> {
+        let api_key = self.get_api_key(request)?;
+
+        if !api_key.is_admin {
+            return Err(AppError::AdminRequired);
+        }
+
+        Ok(api_key.user_id)
+    }
+
+    fn get_api_key<T>(&self, request: &Request<T>) -> AppResult<&ApiKeyConfig> {
+        let api_key = request
+            .metadata()
+            .get(AUTHORIZATION_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix(BEARER_PREFIX))
+            .ok_or(AppError::Unauthenticated)?;
+
+        self.api_keys.get(api_key).ok_or(AppError::Unauthenticated)
+    }
+}
@@ -0,0 +1,96 @@
+use prost::Message;
+use tonic::{Code, Status};
+
+use crate::common::errors::AppError;
+
+use super::server::match_engine::ErrorDetail;
+
+impl From<AppError> for Status {
+    fn from(err: AppError) -> Self {
+        let (code, reason) = match &err {
+            AppError::UserBalanceExceeds => (Code::ResourceExhausted, "USER_BALANCE_EXCEEDS"),
+            AppError::BalanceOverflow => (Code::OutOfRange, "BALANCE_OVERFLOW"),
+            AppError::OrderbookInsertWithNoLimitPrice => {
+                (Code::Internal, "ORDERBOOK_INSERT_WITH_NO_LIMIT_PRICE")
+            }
+            AppError::OrderbookRemoveWithNoLimitPrice => {
+                (Code::Internal, "ORDERBOOK_REMOVE_WITH_NO_LIMIT_PRICE")
+            }
+            AppError::OrderMatchNotFound => (Code::Internal, "ORDER_MATCH_NOT_FOUND"),
+            AppError::MakerOrderWithoutLimitPrice => {
+                (Code::Internal, "MAKER_ORDER_WITHOUT_LIMIT_PRICE")
+            }
+            AppError::OrderOverFilled => (Code::Internal, "ORDER_OVER_FILLED"),
+            AppError::OrderIdDuplication => (Code::AlreadyExists, "ORDER_ID_DUPLICATION"),
+            AppError::OrderIdNotFound => (Code::NotFound, "ORDER_ID_NOT_FOUND"),
+            AppError::OrderInavlidFrozenAmount => (Code::Internal, "ORDER_INVALID_FROZEN_AMOUNT"),
+            AppError::MarketTradeDisbaled => (Code::FailedPrecondition, "MARKET_TRADE_DISABLED"),
+            AppError::MarketMinimumAllowedQuantityExceeds => (
+                Code::InvalidArgument,
+                "MARKET_MINIMUM_ALLOWED_QUANTITY_EXCEEDS",
+            ),
+            AppError::MarketMaximumAllowedQuantityExceeds => (
+                Code::InvalidArgument,
+                "MARKET_MAXIMUM_ALLOWED_QUANTITY_EXCEEDS",
+            ),
+            AppError::OrderPriceTickMismatch => {
+                (Code::InvalidArgument, "ORDER_PRICE_TICK_MISMATCH")
+            }
+            AppError::OrderQuantityStepMismatch => {
+                (Code::InvalidArgument, "ORDER_QUANTITY_STEP_MISMATCH")
+            }
+            AppError::MarketMinimumNotionalExceeds => {
+                (Code::InvalidArgument, "MARKET_MINIMUM_NOTIONAL_EXCEEDS")
+            }
+            AppError::MarketMaximumNotionalExceeds => {
+                (Code::InvalidArgument, "MARKET_MAXIMUM_NOTIONAL_EXCEEDS")
+            }
+            AppError::OrderAmountOverflow => (Code::InvalidArgument, "ORDER_AMOUNT_OVERFLOW"),
+            AppError::LimitOrderInvalidPrice => {
+                (Code::InvalidArgument, "LIMIT_ORDER_INVALID_PRICE")
+            }
+            AppError::CounterOrderbooksIsEmpty => {
+                (Code::FailedPrecondition, "COUNTER_ORDERBOOK_IS_EMPTY")
+            }
+            AppError::InvalidMarketOrderAmount => {
+                (Code::InvalidArgument, "INVALID_MARKET_ORDER_AMOUNT")
+            }
+            AppError::MarketNotFound => (Code::NotFound, "MARKET_NOT_FOUND"),
+            AppError::OrderUserMismatch => (Code::PermissionDenied, "ORDER_USER_MISMATCH"),
+            AppError::FillOrKillOrderNotFilled => {
+                (Code::FailedPrecondition, "FILL_OR_KILL_ORDER_NOT_FILLED")
+            }
+            AppError::PostOnlyOrderInvalid => (Code::InvalidArgument, "POST_ONLY_ORDER_INVALID"),
+            AppError::PostOnlyOrderWouldMatch => {
+                (Code::FailedPrecondition, "POST_ONLY_ORDER_WOULD_MATCH")
+            }
+            AppError::StopOrderInvalidPrice => (Code::InvalidArgument, "STOP_ORDER_INVALID_PRICE"),
+            AppError::BusinessIdConflict => (Code::AlreadyExists, "BUSINESS_ID_CONFLICT"),
+            AppError::MarketOverloaded => (Code::Unavailable, "MARKET_OVERLOADED"),
+            AppError::MarketStopped => (Code::Unavailable, "MARKET_STOPPED"),
+            AppError::JournalFailed(_) => (Code::Internal, "JOURNAL_FAILED"),
+            AppError::SnapshotFailed(_) => (Code::Internal, "SNAPSHOT_FAILED"),
+            AppError::CommandPanicked => (Code::Internal, "COMMAND_PANICKED"),
+            AppError::LedgerFailed(_) => (Code::Internal, "LEDGER_FAILED"),
+            AppError::Unauthenticated => (Code::Unauthenticated, "UNAUTHENTICATED"),
+            AppError::AdminRequired => (Code::PermissionDenied, "ADMIN_REQUIRED"),
+            AppError::SubscriberLagged(_) => (Code::DataLoss, "SUBSCRIBER_LAGGED"),
+            AppError::InvalidRequestField(_, _) => (Code::InvalidArgument, "INVALID_REQUEST_FIELD"),
+        };
+
+        let field = match &err {
+            AppError::InvalidRequestField(field, _) => field.clone(),
+            _ => String::new(),
+        };
+
+        let description = err.to_string();
+
+        let details = ErrorDetail {
+            reason: reason.to_string(),
+            field,
+            description: description.clone(),
+        };
+
+        Status::with_details(code, description, details.encode_to_vec().into())
+    }
+}
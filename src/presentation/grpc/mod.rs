@@ -1,5 +1,10 @@
 use tonic::{Response, Status};
 
+pub mod auth;
+pub mod errors;
+// Every handler has to surface `tonic::Status`, which is larger than clippy likes.
+#[allow(clippy::result_large_err)]
 pub mod server;
+pub mod validation;
 
 pub type GrpcResult<T> = Result<Response<T>, Status>;
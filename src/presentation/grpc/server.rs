@@ -1,47 +1,319 @@
-use std::{str::FromStr, sync::Arc};
+use std::{pin::Pin, sync::Arc};
 
 use rust_decimal::Decimal;
-use tonic::{Request, Response};
+use tokio_stream::{
+    once,
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    Stream, StreamExt,
+};
+use tonic::{Request, Response, Status};
 
 use crate::{
     balance::{
         service::{BalanceService, BusinessType},
         BalanceType,
     },
+    candle::{Candle, CandleInterval},
+    common::{
+        errors::{AppError, AppResult},
+        time::Timestamp,
+    },
     engine::{
-        models::order::{OrderPrice, OrderSide},
+        models::{
+            order::{
+                NewOrder, OrderSide, OrderStatus, PostOnlyMode, SelfTradePreventionMode,
+                TimeInForce,
+            },
+            orderbook::DepthLevel,
+        },
         service::EngineService,
     },
+    fee::FeeRates,
+    ledger::{service::LedgerService, LedgerFilter},
+    notification::{service::NotificationService, OrderEventType, UserEvent},
+    snapshot::service::SnapshotService,
+    ticker::Ticker,
 };
 
 use self::match_engine::{
-    trade_server::Trade, DepositRequest, DepositResponse, GetMarketOrderbookRequest,
-    GetMarketOrderbookResponse, GetUserBalanceRequest, GetUserBalanceResponse, PlaceOrderRequest,
-    PlaceOrderResponse, PriceLevel, WithdrawRequest, WithdrawResponse,
+    orderbook_update, trade_server::Trade, user_event, BalanceChange, BalanceHistoryEntry,
+    CancelOrderRequest, CancelOrderResponse, CandleUpdate, CreateSnapshotRequest,
+    CreateSnapshotResponse, DepositRequest, DepositResponse, ExecutedTrade, GetAllTickersRequest,
+    GetAllTickersResponse, GetBalanceHistoryRequest, GetBalanceHistoryResponse, GetCandlesRequest,
+    GetCandlesResponse, GetMarketOrderbookRequest, GetMarketOrderbookResponse, GetTickerRequest,
+    GetTickerResponse, GetUserBalanceRequest, GetUserBalanceResponse, GetUserFeeTierRequest,
+    GetUserFeeTierResponse, OrderbookSnapshot, OrderbookUpdate, PlaceOrderRequest,
+    PlaceOrderResponse, PriceLevel, PriceLevelDelta, SelfTradeCancel, SetUserFeeOverrideRequest,
+    SetUserFeeOverrideResponse, SubscribeCandlesRequest, SubscribeOrderbookRequest,
+    SubscribeTradesRequest, SubscribeUserEventsRequest, TradeUpdate, WithdrawRequest,
+    WithdrawResponse,
 };
 
-use super::GrpcResult;
+use super::{
+    auth::Authenticator,
+    validation::{
+        parse_business_id, parse_enum, parse_fee_rate, parse_optional_positive_decimal,
+        parse_positive_decimal, parse_taker_fee_rate,
+    },
+    GrpcResult,
+};
+
+const DEFAULT_BALANCE_HISTORY_LIMIT: u32 = 100;
+const MAX_BALANCE_HISTORY_LIMIT: u32 = 1000;
 
 pub mod match_engine {
     tonic::include_proto!("match_engine");
 }
 
+impl From<OrderSide> for match_engine::OrderSide {
+    fn from(value: OrderSide) -> Self {
+        match value {
+            OrderSide::Ask => Self::Ask,
+            OrderSide::Bid => Self::Bid,
+        }
+    }
+}
+
+impl From<match_engine::OrderSide> for OrderSide {
+    fn from(value: match_engine::OrderSide) -> Self {
+        match value {
+            match_engine::OrderSide::Ask => Self::Ask,
+            match_engine::OrderSide::Bid => Self::Bid,
+        }
+    }
+}
+
+impl From<match_engine::TimeInForce> for TimeInForce {
+    fn from(value: match_engine::TimeInForce) -> Self {
+        match value {
+            match_engine::TimeInForce::Gtc => Self::GoodTillCancel,
+            match_engine::TimeInForce::Ioc => Self::ImmediateOrCancel,
+            match_engine::TimeInForce::Fok => Self::FillOrKill,
+        }
+    }
+}
+
+impl From<match_engine::SelfTradePrevention> for Option<SelfTradePreventionMode> {
+    fn from(value: match_engine::SelfTradePrevention) -> Self {
+        match value {
+            match_engine::SelfTradePrevention::MarketDefault => None,
+            match_engine::SelfTradePrevention::CancelNewest => {
+                Some(SelfTradePreventionMode::CancelNewest)
+            }
+            match_engine::SelfTradePrevention::CancelOldest => {
+                Some(SelfTradePreventionMode::CancelOldest)
+            }
+            match_engine::SelfTradePrevention::CancelBoth => {
+                Some(SelfTradePreventionMode::CancelBoth)
+            }
+            match_engine::SelfTradePrevention::DecrementAndCancel => {
+                Some(SelfTradePreventionMode::DecrementAndCancel)
+            }
+        }
+    }
+}
+
+impl From<match_engine::PostOnly> for Option<PostOnlyMode> {
+    fn from(value: match_engine::PostOnly) -> Self {
+        match value {
+            match_engine::PostOnly::Disabled => None,
+            match_engine::PostOnly::Reject => Some(PostOnlyMode::Reject),
+            match_engine::PostOnly::Reprice => Some(PostOnlyMode::Reprice),
+        }
+    }
+}
+
+impl From<BusinessType> for match_engine::BusinessType {
+    fn from(value: BusinessType) -> Self {
+        match value {
+            BusinessType::Withdraw => Self::Withdraw,
+            BusinessType::Deposit => Self::Deposit,
+            BusinessType::Trade => Self::Trade,
+            BusinessType::Order => Self::Order,
+        }
+    }
+}
+
+impl From<match_engine::BusinessType> for BusinessType {
+    fn from(value: match_engine::BusinessType) -> Self {
+        match value {
+            match_engine::BusinessType::Withdraw => Self::Withdraw,
+            match_engine::BusinessType::Deposit => Self::Deposit,
+            match_engine::BusinessType::Trade => Self::Trade,
+            match_engine::BusinessType::Order => Self::Order,
+        }
+    }
+}
+
+impl From<BalanceType> for match_engine::BalanceType {
+    fn from(value: BalanceType) -> Self {
+        match value {
+            BalanceType::Available => Self::Available,
+            BalanceType::Frozen => Self::Frozen,
+        }
+    }
+}
+
+impl From<match_engine::CandleInterval> for CandleInterval {
+    fn from(value: match_engine::CandleInterval) -> Self {
+        match value {
+            match_engine::CandleInterval::OneMinute => Self::OneMinute,
+            match_engine::CandleInterval::FiveMinutes => Self::FiveMinutes,
+            match_engine::CandleInterval::FifteenMinutes => Self::FifteenMinutes,
+            match_engine::CandleInterval::OneHour => Self::OneHour,
+            match_engine::CandleInterval::FourHours => Self::FourHours,
+            match_engine::CandleInterval::OneDay => Self::OneDay,
+        }
+    }
+}
+
+impl From<Ticker> for match_engine::Ticker {
+    fn from(value: Ticker) -> Self {
+        Self {
+            pair_id: value.pair_id,
+            last_price: value.last_price.map(|price| price.to_string()),
+            best_bid: value.best_bid.as_ref().map(to_price_level),
+            best_ask: value.best_ask.as_ref().map(to_price_level),
+            open_price: value.open_price.map(|price| price.to_string()),
+            high_price: value.high_price.map(|price| price.to_string()),
+            low_price: value.low_price.map(|price| price.to_string()),
+            volume: value.volume.to_string(),
+            quote_volume: value.quote_volume.to_string(),
+            trade_count: value.trade_count,
+            price_change: value.price_change.map(|change| change.to_string()),
+            price_change_percent: value.price_change_percent.map(|change| change.to_string()),
+            open_time: value.open_time,
+            close_time: value.close_time,
+        }
+    }
+}
+
+fn to_candle(candle: Candle, interval: CandleInterval) -> match_engine::Candle {
+    match_engine::Candle {
+        open_time: candle.open_time,
+        close_time: candle.get_close_time(interval),
+        open: candle.open.to_string(),
+        high: candle.high.to_string(),
+        low: candle.low.to_string(),
+        close: candle.close.to_string(),
+        volume: candle.volume.to_string(),
+        quote_volume: candle.quote_volume.to_string(),
+        trade_count: candle.trade_count,
+    }
+}
+
+impl From<&OrderEventType> for match_engine::OrderEventType {
+    fn from(value: &OrderEventType) -> Self {
+        match value {
+            OrderEventType::Accepted => Self::Accepted,
+            OrderEventType::PartiallyFilled => Self::PartiallyFilled,
+            OrderEventType::Filled => Self::Filled,
+            OrderEventType::Cancelled => Self::Cancelled,
+            OrderEventType::Rejected(_) => Self::Rejected,
+        }
+    }
+}
+
+impl From<UserEvent> for match_engine::UserEvent {
+    fn from(value: UserEvent) -> Self {
+        let event = match value {
+            UserEvent::Order(event) => user_event::Event::Order(match_engine::OrderEvent {
+                pair_id: event.pair_id,
+                event_type: match_engine::OrderEventType::from(&event.type_).into(),
+                order_id: event.order.get_id(),
+                status: match_engine::OrderStatus::from(event.order.get_status()).into(),
+                side: match_engine::OrderSide::from(event.order.get_side()).into(),
+                limit_price: event
+                    .order
+                    .get_limit_price()
+                    .map(|limit_price| limit_price.to_string())
+                    .unwrap_or_default(),
+                quantity: event.order.get_quantity().to_string(),
+                filled_quantity: event.order.get_filled_quantity().to_string(),
+                reason: match event.type_ {
+                    OrderEventType::Rejected(reason) => reason,
+                    _ => String::new(),
+                },
+                created_at: event.created_at,
+            }),
+            UserEvent::Balance(output) => user_event::Event::Balance(BalanceChange {
+                asset_id: output.asset_id,
+                business_type: match_engine::BusinessType::from(output.business_type).into(),
+                business_id: output.business_id,
+                balance_type: match_engine::BalanceType::from(output.balance_type).into(),
+                amount: output.amount.to_string(),
+                total_balance: output.total_balance.to_string(),
+                available_balance: output.available_balance.to_string(),
+                frozen_balance: output.frozen_balance.to_string(),
+                created_at: output.created_at,
+            }),
+        };
+
+        Self { event: Some(event) }
+    }
+}
+
+impl From<OrderStatus> for match_engine::OrderStatus {
+    fn from(value: OrderStatus) -> Self {
+        match value {
+            OrderStatus::Open => Self::Open,
+            OrderStatus::PartiallyFilled => Self::PartiallyFilled,
+            OrderStatus::Cancelled => Self::Cancelled,
+            OrderStatus::Closed => Self::Closed,
+            OrderStatus::Filled => Self::Filled,
+        }
+    }
+}
+
 pub struct TradeController {
     engine_service: Arc<EngineService>,
     balance_service: Arc<BalanceService>,
+    snapshot_service: Option<Arc<SnapshotService>>,
+    ledger_service: Arc<LedgerService>,
+    notification_service: Arc<NotificationService>,
+    authenticator: Arc<Authenticator>,
 }
 
 impl TradeController {
-    pub fn new(engine_service: Arc<EngineService>, balance_service: Arc<BalanceService>) -> Self {
+    pub fn new(
+        engine_service: Arc<EngineService>,
+        balance_service: Arc<BalanceService>,
+        snapshot_service: Option<Arc<SnapshotService>>,
+        ledger_service: Arc<LedgerService>,
+        notification_service: Arc<NotificationService>,
+        authenticator: Arc<Authenticator>,
+    ) -> Self {
         Self {
             engine_service,
             balance_service,
+            snapshot_service,
+            ledger_service,
+            notification_service,
+            authenticator,
         }
     }
+
+    async fn execute_blocking<T: Send + 'static>(
+        &self,
+        command: impl FnOnce(&EngineService) -> AppResult<T> + Send + 'static,
+    ) -> AppResult<T> {
+        let engine_service = self.engine_service.clone();
+
+        tokio::task::spawn_blocking(move || command(&engine_service))
+            .await
+            .map_err(|err| AppError::JournalFailed(err.to_string()))?
+    }
 }
 
 #[tonic::async_trait]
 impl Trade for TradeController {
+    type SubscribeTradesStream = Pin<Box<dyn Stream<Item = Result<TradeUpdate, Status>> + Send>>;
+    type SubscribeOrderbookStream =
+        Pin<Box<dyn Stream<Item = Result<OrderbookUpdate, Status>> + Send>>;
+    type SubscribeCandlesStream = Pin<Box<dyn Stream<Item = Result<CandleUpdate, Status>> + Send>>;
+    type SubscribeUserEventsStream =
+        Pin<Box<dyn Stream<Item = Result<match_engine::UserEvent, Status>> + Send>>;
+
     async fn get_user_balance(
         &self,
         request: Request<GetUserBalanceRequest>,
@@ -63,38 +335,48 @@ impl Trade for TradeController {
 
     async fn withdraw(&self, request: Request<WithdrawRequest>) -> GrpcResult<WithdrawResponse> {
         let request = request.into_inner();
-        let amount = Decimal::from_str(&request.amount).unwrap();
-
-        self.balance_service
-            .change_balance(
-                request.user_id,
-                request.asset_id,
-                BusinessType::Withdraw,
-                1,
-                BalanceType::Available,
-                -amount,
-            )
-            .unwrap();
+        let amount = parse_positive_decimal("amount", &request.amount)?;
+        let business_id = parse_business_id("business_id", request.business_id)?;
 
-        Ok(Response::new(WithdrawResponse {}))
+        let output = self
+            .execute_blocking(move |engine_service| {
+                engine_service.withdraw(request.user_id, request.asset_id, business_id, amount)
+            })
+            .await?;
+
+        let response = WithdrawResponse {
+            business_id: output.business_id,
+            amount: output.amount.abs().to_string(),
+            total_balance: output.total_balance.to_string(),
+            available_balance: output.available_balance.to_string(),
+            frozen_balance: output.frozen_balance.to_string(),
+            created_at: output.created_at,
+        };
+
+        Ok(Response::new(response))
     }
 
     async fn deposit(&self, request: Request<DepositRequest>) -> GrpcResult<DepositResponse> {
         let request = request.into_inner();
-        let amount = Decimal::from_str(&request.amount).unwrap();
-
-        self.balance_service
-            .change_balance(
-                request.user_id,
-                request.asset_id,
-                BusinessType::Deposit,
-                1,
-                BalanceType::Available,
-                amount,
-            )
-            .unwrap();
+        let amount = parse_positive_decimal("amount", &request.amount)?;
+        let business_id = parse_business_id("business_id", request.business_id)?;
 
-        Ok(Response::new(DepositResponse {}))
+        let output = self
+            .execute_blocking(move |engine_service| {
+                engine_service.deposit(request.user_id, request.asset_id, business_id, amount)
+            })
+            .await?;
+
+        let response = DepositResponse {
+            business_id: output.business_id,
+            amount: output.amount.abs().to_string(),
+            total_balance: output.total_balance.to_string(),
+            available_balance: output.available_balance.to_string(),
+            frozen_balance: output.frozen_balance.to_string(),
+            created_at: output.created_at,
+        };
+
+        Ok(Response::new(response))
     }
 
     async fn place_order(
@@ -103,29 +385,110 @@ impl Trade for TradeController {
     ) -> GrpcResult<PlaceOrderResponse> {
         let request = request.into_inner();
 
-        let limit_price: Option<OrderPrice> = match request.limit_price.is_empty() {
-            true => None,
-            false => Some(Decimal::from_str(&request.limit_price).unwrap()),
+        let limit_price = parse_optional_positive_decimal("limit_price", &request.limit_price)?;
+        let stop_price = parse_optional_positive_decimal("stop_price", &request.stop_price)?;
+        let quote_amount = parse_optional_positive_decimal("quote_amount", &request.quote_amount)?;
+        let quantity = match quote_amount {
+            Some(_) if request.quantity.is_empty() => Decimal::ZERO,
+            Some(_) => {
+                return Err(AppError::InvalidRequestField(
+                    "quantity".to_string(),
+                    "field must be empty when quote_amount is set.".to_string(),
+                )
+                .into())
+            }
+            None => parse_positive_decimal("quantity", &request.quantity)?,
         };
-        let quantity = Decimal::from_str(&request.quantity).unwrap();
 
-        let order_side = match request.side {
-            0 => OrderSide::Ask,
-            1 => OrderSide::Bid,
-            _ => OrderSide::Ask,
-        };
+        let order_side: match_engine::OrderSide = parse_enum("side", request.side)?;
+        let time_in_force: match_engine::TimeInForce =
+            parse_enum("time_in_force", request.time_in_force)?;
+        let post_only: match_engine::PostOnly = parse_enum("post_only", request.post_only)?;
+        let self_trade_prevention: match_engine::SelfTradePrevention =
+            parse_enum("self_trade_prevention", request.self_trade_prevention)?;
 
-        self.engine_service
+        let match_result = self
+            .engine_service
             .place_order(
                 request.pair_id,
-                request.user_id,
-                limit_price,
-                quantity,
-                order_side,
+                NewOrder {
+                    user_id: request.user_id,
+                    side: order_side.into(),
+                    limit_price,
+                    quantity,
+                    time_in_force: time_in_force.into(),
+                    post_only: post_only.into(),
+                    stop_price,
+                    quote_amount,
+                    self_trade_prevention: self_trade_prevention.into(),
+                },
             )
-            .unwrap();
+            .await?;
+
+        let taker_order = match_result.taker_order;
+
+        let trades: Vec<ExecutedTrade> = match_result
+            .trades
+            .iter()
+            .map(|trade| ExecutedTrade {
+                trade_id: trade.get_id(),
+                price: trade.get_price().to_string(),
+                quantity: trade.get_quantity().to_string(),
+                maker_order_id: trade.get_maker_order().get_id(),
+            })
+            .collect();
+
+        let self_trade_cancels: Vec<SelfTradeCancel> = match_result
+            .self_trade_cancels
+            .iter()
+            .map(|self_trade_cancel| SelfTradeCancel {
+                order_id: self_trade_cancel.order.get_id(),
+                quantity: self_trade_cancel.quantity.to_string(),
+                status: match_engine::OrderStatus::from(self_trade_cancel.order.get_status())
+                    .into(),
+            })
+            .collect();
 
-        Ok(Response::new(PlaceOrderResponse { order_id: 0 }))
+        let response = PlaceOrderResponse {
+            order_id: taker_order.get_id(),
+            status: match_engine::OrderStatus::from(taker_order.get_status()).into(),
+            filled_quantity: taker_order.get_filled_quantity().to_string(),
+            remaining_quantity: taker_order.get_remaining_quantity().to_string(),
+            average_price: match_result
+                .get_average_price()
+                .map(|average_price| average_price.to_string())
+                .unwrap_or_default(),
+            trades,
+            self_trade_cancels,
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn cancel_order(
+        &self,
+        request: Request<CancelOrderRequest>,
+    ) -> GrpcResult<CancelOrderResponse> {
+        let request = request.into_inner();
+
+        let order = self
+            .engine_service
+            .cancel_order(request.pair_id, request.user_id, request.order_id)
+            .await?;
+
+        let response = CancelOrderResponse {
+            order_id: order.get_id(),
+            status: match_engine::OrderStatus::from(order.get_status()).into(),
+            side: match_engine::OrderSide::from(order.get_side()).into(),
+            limit_price: order
+                .get_limit_price()
+                .map(|limit_price| limit_price.to_string())
+                .unwrap_or_default(),
+            quantity: order.get_quantity().to_string(),
+            filled_quantity: order.get_filled_quantity().to_string(),
+        };
+
+        Ok(Response::new(response))
     }
 
     async fn get_market_orderbook(
@@ -134,26 +497,328 @@ impl Trade for TradeController {
     ) -> GrpcResult<GetMarketOrderbookResponse> {
         let request = request.into_inner();
 
-        let (asks_depth, bids_depth) = self.engine_service.get_market_orderbook(request.pair_id);
+        let limit = match request.depth {
+            0 => None,
+            depth => Some(depth as usize),
+        };
+        let price_group = parse_optional_positive_decimal("price_group", &request.price_group)?;
 
-        let asks: Vec<PriceLevel> = asks_depth
-            .iter()
-            .map(|value| PriceLevel {
-                price: value[0].to_string(),
-                quantity: value[1].to_string(),
-            })
-            .collect();
+        let state = self
+            .engine_service
+            .get_market_orderbook_state(request.pair_id, limit, price_group)
+            .await?;
 
-        let bids: Vec<PriceLevel> = bids_depth
-            .iter()
-            .map(|value| PriceLevel {
-                price: value[0].to_string(),
-                quantity: value[1].to_string(),
+        let response = GetMarketOrderbookResponse {
+            asks: to_price_levels(&state.asks),
+            bids: to_price_levels(&state.bids),
+            sequence: state.sequence,
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn get_user_fee_tier(
+        &self,
+        request: Request<GetUserFeeTierRequest>,
+    ) -> GrpcResult<GetUserFeeTierResponse> {
+        let request = request.into_inner();
+
+        let fee_status = self
+            .engine_service
+            .get_user_fee_status(request.pair_id, request.user_id)
+            .await?;
+
+        let response = GetUserFeeTierResponse {
+            tier_level: fee_status.tier_level,
+            is_overridden: fee_status.is_overridden,
+            rolling_volume: fee_status.rolling_volume.to_string(),
+            maker_fee_rate: fee_status.rates.maker_fee_rate.to_string(),
+            taker_fee_rate: fee_status.rates.taker_fee_rate.to_string(),
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn set_user_fee_override(
+        &self,
+        request: Request<SetUserFeeOverrideRequest>,
+    ) -> GrpcResult<SetUserFeeOverrideResponse> {
+        self.authenticator.authenticate_admin(&request)?;
+
+        let request = request.into_inner();
+
+        let rates = match request.clear {
+            true => None,
+            false => Some(FeeRates {
+                maker_fee_rate: parse_fee_rate("maker_fee_rate", &request.maker_fee_rate)?,
+                taker_fee_rate: parse_taker_fee_rate("taker_fee_rate", &request.taker_fee_rate)?,
+            }),
+        };
+
+        self.execute_blocking(move |engine_service| {
+            engine_service.set_fee_override(request.user_id, rates)
+        })
+        .await?;
+
+        Ok(Response::new(SetUserFeeOverrideResponse {}))
+    }
+
+    async fn create_snapshot(
+        &self,
+        request: Request<CreateSnapshotRequest>,
+    ) -> GrpcResult<CreateSnapshotResponse> {
+        self.authenticator.authenticate_admin(&request)?;
+
+        let snapshot_service = self
+            .snapshot_service
+            .clone()
+            .ok_or(Status::failed_precondition("Snapshots are not configured."))?;
+
+        let journal_sequence =
+            tokio::task::spawn_blocking(move || snapshot_service.take_snapshot())
+                .await
+                .map_err(|err| AppError::SnapshotFailed(err.to_string()))??;
+
+        Ok(Response::new(CreateSnapshotResponse { journal_sequence }))
+    }
+
+    async fn get_balance_history(
+        &self,
+        request: Request<GetBalanceHistoryRequest>,
+    ) -> GrpcResult<GetBalanceHistoryResponse> {
+        let request = request.into_inner();
+
+        let business_type = match request.business_type {
+            Some(business_type) => Some(
+                parse_enum::<match_engine::BusinessType>("business_type", business_type)?.into(),
+            ),
+            None => None,
+        };
+
+        if let (Some(start_time), Some(end_time)) = (request.start_time, request.end_time) {
+            if start_time > end_time {
+                return Err(AppError::InvalidRequestField(
+                    "end_time".to_string(),
+                    "field must be greater than or equal to start_time.".to_string(),
+                )
+                .into());
+            }
+        }
+
+        let limit = match request.limit {
+            0 => DEFAULT_BALANCE_HISTORY_LIMIT,
+            limit => limit.min(MAX_BALANCE_HISTORY_LIMIT),
+        } as usize;
+
+        let filter = LedgerFilter {
+            user_id: request.user_id,
+            asset_id: request.asset_id,
+            business_type,
+            start_time: request.start_time,
+            end_time: request.end_time,
+            after_id: request.after_id,
+            limit,
+        };
+
+        let ledger_entries = self.ledger_service.query(&filter)?;
+
+        let next_after_id = match ledger_entries.len() == limit {
+            true => ledger_entries.last().map(|entry| entry.id),
+            false => None,
+        };
+
+        let entries: Vec<BalanceHistoryEntry> = ledger_entries
+            .into_iter()
+            .map(|entry| BalanceHistoryEntry {
+                id: entry.id,
+                asset_id: entry.change.asset_id,
+                business_type: match_engine::BusinessType::from(entry.change.business_type).into(),
+                business_id: entry.change.business_id,
+                balance_type: match_engine::BalanceType::from(entry.change.balance_type).into(),
+                amount: entry.change.amount.to_string(),
+                total_balance: entry.change.total_balance.to_string(),
+                available_balance: entry.change.available_balance.to_string(),
+                frozen_balance: entry.change.frozen_balance.to_string(),
+                created_at: entry.change.created_at,
             })
             .collect();
 
-        let response = GetMarketOrderbookResponse { asks, bids };
+        Ok(Response::new(GetBalanceHistoryResponse {
+            entries,
+            next_after_id,
+        }))
+    }
 
-        Ok(Response::new(response))
+    async fn subscribe_trades(
+        &self,
+        request: Request<SubscribeTradesRequest>,
+    ) -> GrpcResult<Self::SubscribeTradesStream> {
+        let request = request.into_inner();
+
+        let receiver = self
+            .engine_service
+            .subscribe_trades(request.pair_id)
+            .await?;
+
+        let stream = BroadcastStream::new(receiver).map(|event| match event {
+            Ok(event) => Ok(TradeUpdate {
+                pair_id: event.pair_id,
+                trade_id: event.trade_id,
+                price: event.price.to_string(),
+                quantity: event.quantity.to_string(),
+                taker_side: match_engine::OrderSide::from(event.taker_side).into(),
+                created_at: event.created_at,
+            }),
+            Err(BroadcastStreamRecvError::Lagged(count)) => {
+                Err(AppError::SubscriberLagged(count).into())
+            }
+        });
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn subscribe_orderbook(
+        &self,
+        request: Request<SubscribeOrderbookRequest>,
+    ) -> GrpcResult<Self::SubscribeOrderbookStream> {
+        let request = request.into_inner();
+        let pair_id = request.pair_id;
+
+        let (state, receiver) = self.engine_service.subscribe_orderbook(pair_id).await?;
+
+        let snapshot = OrderbookUpdate {
+            pair_id,
+            update: Some(orderbook_update::Update::Snapshot(OrderbookSnapshot {
+                sequence: state.sequence,
+                bids: to_price_levels(&state.bids),
+                asks: to_price_levels(&state.asks),
+            })),
+        };
+
+        let deltas = BroadcastStream::new(receiver).map(move |delta| match delta {
+            Ok(delta) => Ok(OrderbookUpdate {
+                pair_id,
+                update: Some(orderbook_update::Update::Delta(PriceLevelDelta {
+                    sequence: delta.sequence,
+                    side: match_engine::OrderSide::from(delta.side).into(),
+                    price: delta.price.to_string(),
+                    quantity: delta.quantity.to_string(),
+                })),
+            }),
+            Err(BroadcastStreamRecvError::Lagged(count)) => {
+                Err(AppError::SubscriberLagged(count).into())
+            }
+        });
+
+        Ok(Response::new(Box::pin(once(Ok(snapshot)).chain(deltas))))
     }
+
+    async fn subscribe_user_events(
+        &self,
+        request: Request<SubscribeUserEventsRequest>,
+    ) -> GrpcResult<Self::SubscribeUserEventsStream> {
+        let user_id = self.authenticator.authenticate(&request)?;
+
+        let receiver = self.notification_service.subscribe(user_id);
+
+        let stream = BroadcastStream::new(receiver).map(|event| match event {
+            Ok(event) => Ok(event.into()),
+            Err(BroadcastStreamRecvError::Lagged(count)) => {
+                Err(AppError::SubscriberLagged(count).into())
+            }
+        });
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn get_candles(
+        &self,
+        request: Request<GetCandlesRequest>,
+    ) -> GrpcResult<GetCandlesResponse> {
+        let request = request.into_inner();
+
+        let interval: CandleInterval =
+            parse_enum::<match_engine::CandleInterval>("interval", request.interval)?.into();
+        let to = request.to.unwrap_or(Timestamp::MAX);
+
+        if request.from > to {
+            return Err(AppError::InvalidRequestField(
+                "to".to_string(),
+                "field must be greater than or equal to from.".to_string(),
+            )
+            .into());
+        }
+
+        let candles =
+            self.engine_service
+                .get_candles(request.pair_id, interval, request.from, to)?;
+
+        Ok(Response::new(GetCandlesResponse {
+            candles: candles
+                .into_iter()
+                .map(|candle| to_candle(candle, interval))
+                .collect(),
+        }))
+    }
+
+    async fn subscribe_candles(
+        &self,
+        request: Request<SubscribeCandlesRequest>,
+    ) -> GrpcResult<Self::SubscribeCandlesStream> {
+        let request = request.into_inner();
+        let pair_id = request.pair_id;
+        let interval: CandleInterval =
+            parse_enum::<match_engine::CandleInterval>("interval", request.interval)?.into();
+
+        let receiver = self.engine_service.subscribe_candles(pair_id, interval)?;
+
+        let stream = BroadcastStream::new(receiver).map(move |candle| match candle {
+            Ok(candle) => Ok(CandleUpdate {
+                pair_id,
+                interval: request.interval,
+                candle: Some(to_candle(candle, interval)),
+            }),
+            Err(BroadcastStreamRecvError::Lagged(count)) => {
+                Err(AppError::SubscriberLagged(count).into())
+            }
+        });
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn get_ticker(
+        &self,
+        request: Request<GetTickerRequest>,
+    ) -> GrpcResult<GetTickerResponse> {
+        let request = request.into_inner();
+
+        let ticker = self.engine_service.get_ticker(request.pair_id).await?;
+
+        Ok(Response::new(GetTickerResponse {
+            ticker: Some(ticker.into()),
+        }))
+    }
+
+    async fn get_all_tickers(
+        &self,
+        _request: Request<GetAllTickersRequest>,
+    ) -> GrpcResult<GetAllTickersResponse> {
+        let tickers = self.engine_service.get_all_tickers().await?;
+
+        Ok(Response::new(GetAllTickersResponse {
+            tickers: tickers.into_iter().map(Into::into).collect(),
+        }))
+    }
+}
+
+fn to_price_level(depth_level: &DepthLevel) -> PriceLevel {
+    PriceLevel {
+        price: depth_level.price.to_string(),
+        quantity: depth_level.quantity.to_string(),
+        order_count: depth_level.order_count as u32,
+    }
+}
+
+fn to_price_levels(depth_levels: &[DepthLevel]) -> Vec<PriceLevel> {
+    depth_levels.iter().map(to_price_level).collect()
 }
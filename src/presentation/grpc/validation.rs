@@ -0,0 +1,85 @@
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+
+use crate::{
+    balance::service::BusinessId,
+    common::errors::{AppError, AppResult},
+};
+
+const MAX_INTEGER_DIGITS: u32 = 12;
+const MAX_SCALE: u32 = 18;
+
+fn invalid_field(field: &str, description: &str) -> AppError {
+    AppError::InvalidRequestField(field.to_string(), description.to_string())
+}
+
+pub fn parse_decimal(field: &str, value: &str) -> AppResult<Decimal> {
+    if value.is_empty() {
+        return Err(invalid_field(field, "field is required."));
+    }
+
+    let decimal = Decimal::from_str(value).map_err(|err| invalid_field(field, &err.to_string()))?;
+
+    if decimal.abs() >= Decimal::from(10_i64.pow(MAX_INTEGER_DIGITS)) {
+        return Err(invalid_field(field, "field is too large."));
+    }
+
+    if decimal.normalize().scale() > MAX_SCALE {
+        return Err(invalid_field(field, "field has too many decimal places."));
+    }
+
+    Ok(decimal)
+}
+
+pub fn parse_positive_decimal(field: &str, value: &str) -> AppResult<Decimal> {
+    let decimal = parse_decimal(field, value)?;
+
+    if decimal <= Decimal::ZERO {
+        return Err(invalid_field(field, "field must be greater than zero."));
+    }
+
+    Ok(decimal)
+}
+
+pub fn parse_optional_positive_decimal(field: &str, value: &str) -> AppResult<Option<Decimal>> {
+    match value.is_empty() {
+        true => Ok(None),
+        false => parse_positive_decimal(field, value).map(Some),
+    }
+}
+
+pub fn parse_fee_rate(field: &str, value: &str) -> AppResult<Decimal> {
+    let decimal = parse_decimal(field, value)?;
+
+    if decimal <= -Decimal::ONE || decimal >= Decimal::ONE {
+        return Err(invalid_field(
+            field,
+            "field must be greater than minus one and less than one.",
+        ));
+    }
+
+    Ok(decimal)
+}
+
+pub fn parse_taker_fee_rate(field: &str, value: &str) -> AppResult<Decimal> {
+    let decimal = parse_fee_rate(field, value)?;
+
+    if decimal < Decimal::ZERO {
+        return Err(invalid_field(field, "field must not be negative."));
+    }
+
+    Ok(decimal)
+}
+
+pub fn parse_business_id(field: &str, value: BusinessId) -> AppResult<BusinessId> {
+    if value == 0 {
+        return Err(invalid_field(field, "field is required."));
+    }
+
+    Ok(value)
+}
+
+pub fn parse_enum<T: TryFrom<i32>>(field: &str, value: i32) -> AppResult<T> {
+    T::try_from(value).map_err(|_| invalid_field(field, "field has an unknown enum value."))
+}
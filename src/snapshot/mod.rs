@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    balance::{service::ChangeBalanceOutput, BalanceEntry},
+    candle::CandleSeries,
+    engine::models::{
+        market::{MarketSnapshot, PairId},
+        trade::TradeId,
+    },
+    fee::FeeState,
+    journal::JournalSequence,
+};
+
+pub mod service;
+
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+pub struct EngineSnapshot {
+    pub version: u32,
+    pub journal_sequence: JournalSequence,
+    pub markets: HashMap<PairId, MarketSnapshot>,
+    #[serde(default)]
+    pub trade_id_sequence: TradeId,
+    pub balances: Vec<BalanceEntry>,
+    #[serde(default)]
+    pub business_outputs: Vec<ChangeBalanceOutput>,
+    pub fees: FeeState,
+    #[serde(default)]
+    pub candles: Vec<CandleSeries>,
+}
@@ -0,0 +1,132 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use crate::{
+    common::errors::{AppError, AppResult},
+    engine::service::EngineService,
+    journal::JournalSequence,
+};
+
+use super::EngineSnapshot;
+
+const SNAPSHOT_FILE_PREFIX: &str = "snapshot-";
+const SNAPSHOT_FILE_EXTENSION: &str = ".json";
+const DEFAULT_RETAIN_COUNT: usize = 3;
+
+pub struct SnapshotService {
+    engine_service: Arc<EngineService>,
+    path: PathBuf,
+    retain_count: usize,
+}
+
+impl SnapshotService {
+    pub fn new(
+        engine_service: Arc<EngineService>,
+        path: &str,
+        retain_count: Option<usize>,
+    ) -> Self {
+        Self {
+            engine_service,
+            path: PathBuf::from(path),
+            retain_count: retain_count.unwrap_or(DEFAULT_RETAIN_COUNT).max(1),
+        }
+    }
+
+    pub fn take_snapshot(&self) -> AppResult<JournalSequence> {
+        let snapshot = self.engine_service.create_snapshot()?;
+        let journal_sequence = snapshot.journal_sequence;
+
+        fs::create_dir_all(&self.path).map_err(Self::io_error)?;
+
+        let file_path = self.path.join(format!(
+            "{}{:020}{}",
+            SNAPSHOT_FILE_PREFIX, journal_sequence, SNAPSHOT_FILE_EXTENSION
+        ));
+        let temp_file_path = file_path.with_extension("tmp");
+
+        let file = File::create(&temp_file_path).map_err(Self::io_error)?;
+        let mut writer = BufWriter::new(file);
+
+        serde_json::to_writer(&mut writer, &snapshot)
+            .map_err(|err| AppError::SnapshotFailed(err.to_string()))?;
+
+        let file = writer
+            .into_inner()
+            .map_err(|err| AppError::SnapshotFailed(err.to_string()))?;
+        file.sync_all().map_err(Self::io_error)?;
+
+        fs::rename(&temp_file_path, &file_path).map_err(Self::io_error)?;
+
+        self.prune_snapshots()?;
+
+        Ok(journal_sequence)
+    }
+
+    fn prune_snapshots(&self) -> AppResult<()> {
+        let snapshots = self.list_snapshots()?;
+        let prune_count = snapshots.len().saturating_sub(self.retain_count);
+
+        for (_, path) in &snapshots[..prune_count] {
+            fs::remove_file(path).map_err(Self::io_error)?;
+        }
+
+        match snapshots.get(prune_count) {
+            Some((oldest_sequence, _)) => self.engine_service.truncate_journal(*oldest_sequence),
+            None => Ok(()),
+        }
+    }
+
+    pub fn restore_latest_snapshot(&self) -> AppResult<Option<JournalSequence>> {
+        let Some(file_path) = self.find_latest_snapshot()? else {
+            return Ok(None);
+        };
+
+        let file = File::open(file_path).map_err(Self::io_error)?;
+        let snapshot: EngineSnapshot = serde_json::from_reader(BufReader::new(file))
+            .map_err(|err| AppError::SnapshotFailed(err.to_string()))?;
+        let journal_sequence = snapshot.journal_sequence;
+
+        self.engine_service.restore_snapshot(snapshot)?;
+
+        Ok(Some(journal_sequence))
+    }
+
+    fn find_latest_snapshot(&self) -> AppResult<Option<PathBuf>> {
+        Ok(self.list_snapshots()?.pop().map(|(_, path)| path))
+    }
+
+    fn list_snapshots(&self) -> AppResult<Vec<(JournalSequence, PathBuf)>> {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut snapshots = vec![];
+
+        for entry in fs::read_dir(&self.path).map_err(Self::io_error)? {
+            let entry = entry.map_err(Self::io_error)?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+
+            let Some(journal_sequence) = file_name
+                .strip_prefix(SNAPSHOT_FILE_PREFIX)
+                .and_then(|name| name.strip_suffix(SNAPSHOT_FILE_EXTENSION))
+                .and_then(|sequence| sequence.parse::<JournalSequence>().ok())
+            else {
+                continue;
+            };
+
+            snapshots.push((journal_sequence, entry.path()));
+        }
+
+        snapshots.sort_by_key(|(journal_sequence, _)| *journal_sequence);
+
+        Ok(snapshots)
+    }
+
+    fn io_error(err: std::io::Error) -> AppError {
+        AppError::SnapshotFailed(err.to_string())
+    }
+}
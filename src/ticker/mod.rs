@@ -0,0 +1,72 @@
+use rust_decimal::Decimal;
+
+use crate::{
+    candle::Candle,
+    common::time::Timestamp,
+    engine::models::{market::PairId, order::OrderPrice, orderbook::DepthLevel},
+};
+
+pub const TICKER_WINDOW: Timestamp = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone)]
+pub struct Ticker {
+    pub pair_id: PairId,
+    pub last_price: Option<OrderPrice>,
+    pub best_bid: Option<DepthLevel>,
+    pub best_ask: Option<DepthLevel>,
+    pub open_price: Option<OrderPrice>,
+    pub high_price: Option<OrderPrice>,
+    pub low_price: Option<OrderPrice>,
+    pub volume: Decimal,
+    pub quote_volume: Decimal,
+    pub trade_count: u64,
+    pub price_change: Option<Decimal>,
+    pub price_change_percent: Option<Decimal>,
+    pub open_time: Timestamp,
+    pub close_time: Timestamp,
+}
+
+impl Ticker {
+    pub fn new(
+        pair_id: PairId,
+        last_price: Option<OrderPrice>,
+        best_bid: Option<DepthLevel>,
+        best_ask: Option<DepthLevel>,
+        window: Option<Candle>,
+        open_time: Timestamp,
+        close_time: Timestamp,
+    ) -> Self {
+        let mut ticker = Self {
+            pair_id,
+            last_price,
+            best_bid,
+            best_ask,
+            open_price: None,
+            high_price: None,
+            low_price: None,
+            volume: Decimal::ZERO,
+            quote_volume: Decimal::ZERO,
+            trade_count: 0,
+            price_change: None,
+            price_change_percent: None,
+            open_time,
+            close_time,
+        };
+
+        if let Some(window) = window {
+            let price_change = window.close - window.open;
+
+            ticker.open_price = Some(window.open);
+            ticker.high_price = Some(window.high);
+            ticker.low_price = Some(window.low);
+            ticker.volume = window.volume;
+            ticker.quote_volume = window.quote_volume;
+            ticker.trade_count = window.trade_count;
+            ticker.price_change = Some(price_change);
+            ticker.price_change_percent = (!window.open.is_zero())
+                .then(|| (price_change / window.open * Decimal::ONE_HUNDRED).round_dp(2));
+        }
+
+        ticker
+    }
+}